@@ -0,0 +1,73 @@
+//! Benchmarks of the binary over a large synthetic file
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Items of each kind in the synthetic file
+const ITEMS: usize = 5000;
+
+/// Writes a file with `n` documented structs, each with an impl and a free
+/// function, to the target's temporary directory
+fn synthetic(n: usize) -> PathBuf {
+    let mut text = String::new();
+    for i in 0..n {
+        text += &format!(
+            "/// Item {0}\n\
+             #[derive(Debug, Clone)]\n\
+             pub struct S{0} {{\n    a: u32,\n    b: Vec<String>,\n}}\n\n\
+             impl S{0} {{\n    pub fn new() -> Self {{\n        \
+             Self {{ a: {0}, b: vec![] }}\n    }}\n}}\n\n\
+             pub fn f{0}(x: u32) -> u32 {{\n    \
+             if x > {0} {{ x - 1 }} else {{ x + 1 }}\n}}\n\n",
+            i
+        );
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join(format!("synthetic_{}.rs", n));
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn run(args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_code-extractor"))
+        .args(args)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn extract(c: &mut Criterion) {
+    let path = synthetic(ITEMS);
+    let path = path.to_str().unwrap();
+    let last = format!("f{}", ITEMS - 1);
+    c.bench_function("function", |b| {
+        b.iter(|| run(&[path, "function", &last]))
+    });
+    let method = format!("S{}::new", ITEMS / 2);
+    c.bench_function("method", |b| {
+        b.iter(|| run(&[path, "function", &method]))
+    });
+    c.bench_function("function --keep-comments", |b| {
+        b.iter(|| run(&[path, "--keep-comments", "function", &last]))
+    });
+}
+
+/// Listing writes a line per item, which is where buffering the output
+/// matters
+fn list(c: &mut Criterion) {
+    let path = synthetic(ITEMS);
+    let path = path.to_str().unwrap();
+    c.bench_function("list", |b| b.iter(|| run(&[path, "list"])));
+    c.bench_function("list --tsv", |b| {
+        b.iter(|| run(&[path, "list", "--tsv"]))
+    });
+}
+
+criterion_group!(benches, extract, list);
+criterion_main!(benches);
@@ -0,0 +1,254 @@
+use std::{collections::BTreeMap, io::Write};
+
+use syn::{
+    parse_quote, Block, Fields, File, ImplItem, Item, ItemFn, Visibility,
+};
+
+use crate::{
+    error::Result, impl_name, item_attrs_mut, item_info, unparse_type, Unparse,
+};
+
+/// Counts the `pub` items reachable from the crate root through `pub mod`s,
+/// by kind. Modules in other files aren't followed.
+fn count(file: &File) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    count_items(&file.items, &mut counts);
+    counts
+}
+
+fn count_items(items: &[Item], counts: &mut BTreeMap<&'static str, usize>) {
+    for item in items {
+        if !matches!(visibility(item), Some(Visibility::Public(_))) {
+            continue;
+        }
+        match (item_info(item), item) {
+            (Some((kind, _)), _) => *counts.entry(kind).or_default() += 1,
+            (None, Item::Mod(m)) => {
+                if let Some((_, content)) = &m.content {
+                    count_items(content, counts);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn visibility(item: &Item) -> Option<&Visibility> {
+    match item {
+        Item::Const(i) => Some(&i.vis),
+        Item::Enum(i) => Some(&i.vis),
+        Item::ExternCrate(i) => Some(&i.vis),
+        Item::Fn(i) => Some(&i.vis),
+        Item::Mod(i) => Some(&i.vis),
+        Item::Static(i) => Some(&i.vis),
+        Item::Struct(i) => Some(&i.vis),
+        Item::Trait(i) => Some(&i.vis),
+        Item::TraitAlias(i) => Some(&i.vis),
+        Item::Type(i) => Some(&i.vis),
+        Item::Union(i) => Some(&i.vis),
+        Item::Use(i) => Some(&i.vis),
+        _ => None,
+    }
+}
+
+pub fn print(file: &File, json: bool, out: &mut dyn Write) -> Result<()> {
+    let counts = count(file);
+    let total: usize = counts.values().sum();
+    if json {
+        let kinds: Vec<_> = counts
+            .iter()
+            .map(|(kind, n)| format!("\"{}\":{}", kind, n))
+            .collect();
+        writeln!(
+            out,
+            "{{\"total\":{},\"kinds\":{{{}}}}}",
+            total,
+            kinds.join(",")
+        )?;
+        return Ok(());
+    }
+    writeln!(out, "{} public items", total)?;
+    for (kind, n) in counts {
+        writeln!(out, "{:>12} {}", kind, n)?;
+    }
+    Ok(())
+}
+
+/// A public item in the API outline, with the public items of modules and
+/// the public methods of inherent impls under it
+struct Node {
+    kind: &'static str,
+    name: String,
+    signature: String,
+    items: Vec<Node>,
+}
+
+/// Walks the items like `count_items`, keeping the signatures
+fn api_nodes(items: &[Item]) -> Vec<Node> {
+    let mut nodes = vec![];
+    for item in items {
+        if let Item::Impl(i) = item {
+            let methods: Vec<_> = i
+                .items
+                .iter()
+                .filter_map(|member| match member {
+                    ImplItem::Method(m)
+                        if matches!(m.vis, Visibility::Public(_)) =>
+                    {
+                        let f = Item::Fn(ItemFn {
+                            attrs: vec![],
+                            vis: m.vis.clone(),
+                            sig: m.sig.clone(),
+                            block: Box::new(parse_quote!({})),
+                        });
+                        Some(Node {
+                            kind: "fn",
+                            name: m.sig.ident.to_string(),
+                            signature: signature(f),
+                            items: vec![],
+                        })
+                    }
+                    _ => None,
+                })
+                .collect();
+            if i.trait_.is_none() && !methods.is_empty() {
+                let mut header = i.clone();
+                header.attrs.clear();
+                header.items.clear();
+                nodes.push(Node {
+                    kind: "impl",
+                    name: impl_name(i),
+                    signature: signature(Item::Impl(header)),
+                    items: methods,
+                });
+            }
+            continue;
+        }
+        if !matches!(visibility(item), Some(Visibility::Public(_))) {
+            continue;
+        }
+        let (kind, name, items) = match (item_info(item), item) {
+            (Some((kind, name)), _) => (kind, name.to_string(), vec![]),
+            (None, Item::Mod(m)) => {
+                let items =
+                    m.content.as_ref().map_or(vec![], |(_, c)| api_nodes(c));
+                ("mod", m.ident.to_string(), items)
+            }
+            (None, Item::Use(_)) => ("use", String::new(), vec![]),
+            _ => continue,
+        };
+        nodes.push(Node {
+            kind,
+            name,
+            signature: signature(item.clone()),
+            items,
+        });
+    }
+    nodes
+}
+
+/// Renders the declaration of an item without its body, fields, variants
+/// or value, on one line, like `pub fn parse(s: &str) -> Result<Ast>`
+fn signature(mut item: Item) -> String {
+    let empty: Block = parse_quote!({});
+    match &mut item {
+        Item::Fn(i) => *i.block = empty,
+        Item::Struct(i) => {
+            i.fields = Fields::Unit;
+            i.semi_token = Some(Default::default());
+        }
+        Item::Enum(i) => i.variants.clear(),
+        Item::Union(i) => i.fields.named.clear(),
+        Item::Trait(i) => i.items.clear(),
+        Item::Mod(i) => return format!("pub mod {}", i.ident),
+        Item::Const(i) => {
+            return format!("pub const {}: {}", i.ident, unparse_type(&i.ty))
+        }
+        Item::Static(i) => {
+            let mutability = if i.mutability.is_some() { "mut " } else { "" };
+            let ty = unparse_type(&i.ty);
+            return format!("pub static {}{}: {}", mutability, i.ident, ty);
+        }
+        _ => {}
+    }
+    if let Some(attrs) = item_attrs_mut(&mut item) {
+        attrs.clear();
+    }
+    let text = item.unparse();
+    let text = one_line(&text);
+    let text = text.strip_suffix("{}").unwrap_or(&text);
+    text.trim_end_matches([';', ' ']).to_string()
+}
+
+/// Joins the lines prettyplease broke a long declaration into, dropping
+/// the trailing commas before closing brackets
+fn one_line(text: &str) -> String {
+    let mut joined = String::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with([')', '>', '}']) {
+            joined.truncate(joined.trim_end_matches([',', ' ']).len());
+        } else if !(joined.is_empty() || joined.ends_with(['(', '<', '{'])) {
+            joined.push(' ');
+        }
+        joined += line;
+    }
+    joined
+}
+
+/// Prints the public items reachable from the crate root as a tree of
+/// signatures, as text indented by module or as nested JSON objects
+pub fn tree(file: &File, json: bool, out: &mut dyn Write) -> Result<()> {
+    let nodes = api_nodes(&file.items);
+    if json {
+        writeln!(out, "{}", json_nodes(&nodes))?;
+        return Ok(());
+    }
+    if nodes.is_empty() {
+        writeln!(out, "No public items")?;
+    }
+    print_nodes(&nodes, 0, out)
+}
+
+fn print_nodes(
+    nodes: &[Node],
+    depth: usize,
+    out: &mut dyn Write,
+) -> Result<()> {
+    for node in nodes {
+        writeln!(out, "{:w$}{}", "", node.signature, w = depth * 4)?;
+        print_nodes(&node.items, depth + 1, out)?;
+    }
+    Ok(())
+}
+
+fn json_nodes(nodes: &[Node]) -> String {
+    let nodes: Vec<_> = nodes
+        .iter()
+        .map(|node| {
+            format!(
+                "{{\"kind\":{},\"name\":{},\"signature\":{},\"items\":{}}}",
+                json_string(node.kind),
+                json_string(&node.name),
+                json_string(&node.signature),
+                json_nodes(&node.items)
+            )
+        })
+        .collect();
+    format!("[{}]", nodes.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if c.is_control() => {
+                quoted += &format!("\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,251 @@
+use std::io::Write;
+
+use syn::{
+    parse_quote,
+    spanned::Spanned,
+    visit::{self, Visit},
+    ExprUnsafe, File, ImplItemMethod, Item, ItemFn, ItemImpl, ItemStruct,
+    ItemTrait, Macro, Signature, TraitItemMethod, Visibility,
+};
+
+use crate::{
+    error::{Error, Result},
+    impl_name, type_name, unparse_type, Unparse,
+};
+
+/// Prints every `unsafe` fn, block, impl and trait in the file with its line,
+/// then the total count
+pub fn unsafe_report(file: &File, out: &mut dyn Write) -> Result<()> {
+    let mut audit = Audit::default();
+    audit.visit_file(file);
+    for (line, what) in &audit.found {
+        writeln!(out, "{:>5}: {}", line, what)?;
+    }
+    writeln!(out, "{} unsafe occurrences", audit.found.len())?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Audit {
+    found: Vec<(usize, String)>,
+    /// Name of the innermost function being visited
+    fn_name: Option<String>,
+}
+
+impl Audit {
+    fn visit_fn(&mut self, sig: &Signature, visit: impl FnOnce(&mut Self)) {
+        if sig.unsafety.is_some() {
+            let line = sig.span().start().line;
+            self.found.push((line, format!("unsafe fn {}", sig.ident)));
+        }
+        let outer = self.fn_name.replace(sig.ident.to_string());
+        visit(self);
+        self.fn_name = outer;
+    }
+}
+
+impl<'ast> Visit<'ast> for Audit {
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        self.visit_fn(&i.sig, |v| visit::visit_item_fn(v, i));
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        self.visit_fn(&i.sig, |v| visit::visit_impl_item_method(v, i));
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        self.visit_fn(&i.sig, |v| visit::visit_trait_item_method(v, i));
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        if i.unsafety.is_some() {
+            let line = i.span().start().line;
+            self.found
+                .push((line, format!("unsafe impl {}", impl_name(i))));
+        }
+        visit::visit_item_impl(self, i);
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        if i.unsafety.is_some() {
+            let line = i.span().start().line;
+            self.found.push((line, format!("unsafe trait {}", i.ident)));
+        }
+        visit::visit_item_trait(self, i);
+    }
+
+    fn visit_expr_unsafe(&mut self, i: &'ast ExprUnsafe) {
+        let what = match &self.fn_name {
+            Some(name) => format!("unsafe block in fn {}", name),
+            None => "unsafe block".to_string(),
+        };
+        self.found.push((i.span().start().line, what));
+        visit::visit_expr_unsafe(self, i);
+    }
+}
+
+/// Macros marking unfinished code or possible panics
+const PANIC_MACROS: [&str; 3] = ["todo", "unimplemented", "panic"];
+
+/// Prints the functions and methods calling `todo!`, `unimplemented!` or
+/// `panic!` with their line and how often they call each, then their count
+pub fn panic_report(file: &File, out: &mut dyn Write) -> Result<()> {
+    let mut panics = Panics::default();
+    panics.visit_file(file);
+    for (line, name, counts) in &panics.found {
+        let counts: Vec<_> = PANIC_MACROS
+            .iter()
+            .zip(counts)
+            .filter(|(_, n)| **n > 0)
+            .map(|(mac, n)| format!("{} {}!", n, mac))
+            .collect();
+        writeln!(out, "{:>5}: fn {}: {}", line, name, counts.join(", "))?;
+    }
+    let plural = if panics.found.len() == 1 { "" } else { "s" };
+    writeln!(
+        out,
+        "{} function{} with todo!, unimplemented! or panic!",
+        panics.found.len(),
+        plural
+    )?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Panics {
+    /// Line, name and count of each macro of the functions calling them
+    found: Vec<(usize, String, [usize; 3])>,
+    /// Self type of the impl being visited, for naming methods
+    impl_name: Option<String>,
+    /// Counts of the innermost function being visited
+    counts: Option<[usize; 3]>,
+}
+
+impl Panics {
+    fn visit_fn(&mut self, sig: &Signature, visit: impl FnOnce(&mut Self)) {
+        let outer = self.counts.replace([0; 3]);
+        // before the functions inside it
+        let index = self.found.len();
+        visit(self);
+        let counts = std::mem::replace(&mut self.counts, outer).unwrap();
+        if counts.iter().any(|n| *n > 0) {
+            let name = match &self.impl_name {
+                Some(ty) => format!("{}::{}", ty, sig.ident),
+                None => sig.ident.to_string(),
+            };
+            let line = sig.span().start().line;
+            self.found.insert(index, (line, name, counts));
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Panics {
+    fn visit_item_fn(&mut self, i: &'ast ItemFn) {
+        // functions inside methods aren't methods
+        let outer = self.impl_name.take();
+        self.visit_fn(&i.sig, |v| visit::visit_item_fn(v, i));
+        self.impl_name = outer;
+    }
+
+    fn visit_impl_item_method(&mut self, i: &'ast ImplItemMethod) {
+        self.visit_fn(&i.sig, |v| visit::visit_impl_item_method(v, i));
+    }
+
+    fn visit_trait_item_method(&mut self, i: &'ast TraitItemMethod) {
+        self.visit_fn(&i.sig, |v| visit::visit_trait_item_method(v, i));
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast ItemImpl) {
+        let ty = type_name(&i.self_ty).map(ToString::to_string);
+        let outer = std::mem::replace(&mut self.impl_name, ty);
+        visit::visit_item_impl(self, i);
+        self.impl_name = outer;
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        let outer = self.impl_name.replace(i.ident.to_string());
+        visit::visit_item_trait(self, i);
+        self.impl_name = outer;
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        let name = mac.path.segments.last().map(|s| s.ident.to_string());
+        let index =
+            name.and_then(|n| PANIC_MACROS.iter().position(|m| *m == n));
+        if let (Some(counts), Some(i)) = (&mut self.counts, index) {
+            counts[i] += 1;
+        }
+        visit::visit_macro(self, mac);
+    }
+}
+
+/// Prints how many fields of the struct `name`, or of every top-level struct
+/// with fields, are visible outside it, listing those, then the total
+pub fn field_vis(
+    file: &File,
+    name: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let structs: Vec<_> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Struct(s) if name.is_none_or(|n| s.ident == n) => Some(s),
+            _ => None,
+        })
+        .filter(|s| !s.fields.is_empty())
+        .collect();
+    if let (Some(name), []) = (name, &structs[..]) {
+        return Err(Error::not_found(format!("struct `{}` with fields", name)));
+    }
+    let (mut visible, mut total) = (0, 0);
+    for s in &structs {
+        let fields = visible_fields(s);
+        let plural = if s.fields.len() == 1 { "" } else { "s" };
+        writeln!(
+            out,
+            "{}: {} of {} field{} public",
+            s.ident,
+            fields.len(),
+            s.fields.len(),
+            plural
+        )?;
+        for field in &fields {
+            writeln!(out, "    {}", field)?;
+        }
+        visible += fields.len();
+        total += s.fields.len();
+    }
+    if name.is_none() {
+        let fields_plural = if total == 1 { "" } else { "s" };
+        let plural = if structs.len() == 1 { "" } else { "s" };
+        writeln!(
+            out,
+            "{} of {} field{} public in {} struct{}",
+            visible,
+            total,
+            fields_plural,
+            structs.len(),
+            plural
+        )?;
+    }
+    Ok(())
+}
+
+/// The fields with any visibility, including `pub(crate)`, rendered like
+/// `pub(crate) len: usize`, with indices for tuple structs
+fn visible_fields(s: &ItemStruct) -> Vec<String> {
+    let fields = s.fields.iter().enumerate();
+    fields
+        .filter(|(_, f)| !matches!(f.vis, Visibility::Inherited))
+        .map(|(i, f)| {
+            let name =
+                f.ident.as_ref().map_or(i.to_string(), |n| n.to_string());
+            let vis = &f.vis;
+            let vis: Item = parse_quote!(#vis struct _X;);
+            let vis = vis.unparse();
+            let vis = vis.trim_end().strip_suffix("struct _X;").unwrap();
+            format!("{}{}: {}", vis, name, unparse_type(&f.ty))
+        })
+        .collect()
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use syn::{
+    spanned::Spanned,
+    visit_mut::{self, VisitMut},
+    Arm, Attribute, Block, Expr, File, ForeignItem, ImplItem, Item,
+    ItemForeignMod, ItemImpl, ItemMod, ItemTrait, Lit, Meta, NestedMeta, Stmt,
+    TraitItem,
+};
+
+use crate::item_attrs;
+
+/// A set of enabled cfg options, as passed with `--cfg`
+pub struct CfgSet {
+    names: HashSet<String>,
+    pairs: HashSet<(String, String)>,
+}
+
+impl CfgSet {
+    /// Parses options like `unix`, `feature="std"` or `feature=std`
+    pub fn new(options: &[String]) -> Self {
+        let mut names = HashSet::new();
+        let mut pairs = HashSet::new();
+        for option in options {
+            match option.split_once('=') {
+                Some((key, value)) => {
+                    let value = value.trim().trim_matches('"');
+                    pairs.insert((key.trim().to_string(), value.to_string()));
+                }
+                None => {
+                    names.insert(option.trim().to_string());
+                }
+            }
+        }
+        Self { names, pairs }
+    }
+
+    /// Whether all `#[cfg]` attributes in `attrs` are satisfied. Predicates
+    /// that can't be parsed count as satisfied.
+    pub fn is_enabled(&self, attrs: &[Attribute]) -> bool {
+        attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("cfg"))
+            .all(|attr| match attr.parse_meta() {
+                Ok(Meta::List(list)) if list.nested.len() == 1 => {
+                    self.eval(&list.nested[0]).unwrap_or(true)
+                }
+                _ => true,
+            })
+    }
+
+    fn eval(&self, predicate: &NestedMeta) -> Option<bool> {
+        match predicate {
+            NestedMeta::Meta(Meta::Path(path)) => {
+                Some(self.names.contains(&path.get_ident()?.to_string()))
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) => {
+                let Lit::Str(value) = &nv.lit else {
+                    return None;
+                };
+                let key = nv.path.get_ident()?.to_string();
+                Some(self.pairs.contains(&(key, value.value())))
+            }
+            NestedMeta::Meta(Meta::List(list)) => {
+                let mut args = list.nested.iter().map(|p| self.eval(p));
+                match list.path.get_ident()?.to_string().as_str() {
+                    "all" => args.try_fold(true, |acc, x| Some(acc && x?)),
+                    "any" => args.try_fold(false, |acc, x| Some(acc || x?)),
+                    "not" if list.nested.len() == 1 => Some(!args.next()??),
+                    _ => None,
+                }
+            }
+            NestedMeta::Lit(_) => None,
+        }
+    }
+
+    /// Removes all items, statements, fields, variants and match arms that
+    /// are disabled under this cfg set
+    pub fn strip(&self, file: &mut File) {
+        Strip(self).visit_file_mut(file);
+    }
+}
+
+/// The inline modules `item` is in, outermost first, found by its position
+pub fn enclosing<'a>(items: &'a [Item], item: &Item) -> Vec<&'a ItemMod> {
+    let start = item.span().start();
+    for i in items {
+        if let Item::Mod(
+            m @ ItemMod {
+                content: Some((_, inner)),
+                ..
+            },
+        ) = i
+        {
+            if inner.iter().any(|i| i.span().start() == start) {
+                return vec![m];
+            }
+            let mut mods = enclosing(inner, item);
+            if !mods.is_empty() {
+                mods.insert(0, m);
+                return mods;
+            }
+        }
+    }
+    vec![]
+}
+
+struct Strip<'a>(&'a CfgSet);
+
+impl VisitMut for Strip<'_> {
+    fn visit_file_mut(&mut self, file: &mut File) {
+        file.items
+            .retain(|item| self.0.is_enabled(item_attrs(item)));
+        visit_mut::visit_file_mut(self, file);
+    }
+
+    fn visit_item_mod_mut(&mut self, m: &mut ItemMod) {
+        if let Some((_, items)) = &mut m.content {
+            items.retain(|item| self.0.is_enabled(item_attrs(item)));
+        }
+        visit_mut::visit_item_mod_mut(self, m);
+    }
+
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        i.items.retain(|item| {
+            self.0.is_enabled(match item {
+                ImplItem::Const(i) => &i.attrs,
+                ImplItem::Method(i) => &i.attrs,
+                ImplItem::Type(i) => &i.attrs,
+                ImplItem::Macro(i) => &i.attrs,
+                _ => &[],
+            })
+        });
+        visit_mut::visit_item_impl_mut(self, i);
+    }
+
+    fn visit_item_trait_mut(&mut self, t: &mut ItemTrait) {
+        t.items.retain(|item| {
+            self.0.is_enabled(match item {
+                TraitItem::Const(i) => &i.attrs,
+                TraitItem::Method(i) => &i.attrs,
+                TraitItem::Type(i) => &i.attrs,
+                TraitItem::Macro(i) => &i.attrs,
+                _ => &[],
+            })
+        });
+        visit_mut::visit_item_trait_mut(self, t);
+    }
+
+    fn visit_item_foreign_mod_mut(&mut self, m: &mut ItemForeignMod) {
+        m.items.retain(|item| {
+            self.0.is_enabled(match item {
+                ForeignItem::Fn(i) => &i.attrs,
+                ForeignItem::Static(i) => &i.attrs,
+                ForeignItem::Type(i) => &i.attrs,
+                ForeignItem::Macro(i) => &i.attrs,
+                _ => &[],
+            })
+        });
+        visit_mut::visit_item_foreign_mod_mut(self, m);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        block.stmts.retain(|stmt| {
+            self.0.is_enabled(match stmt {
+                Stmt::Local(l) => &l.attrs,
+                Stmt::Item(i) => item_attrs(i),
+                Stmt::Expr(e) | Stmt::Semi(e, _) => expr_attrs(e),
+            })
+        });
+        visit_mut::visit_block_mut(self, block);
+    }
+
+    fn visit_fields_named_mut(&mut self, fields: &mut syn::FieldsNamed) {
+        fields.named = std::mem::take(&mut fields.named)
+            .into_pairs()
+            .filter(|p| self.0.is_enabled(&p.value().attrs))
+            .collect();
+        visit_mut::visit_fields_named_mut(self, fields);
+    }
+
+    fn visit_fields_unnamed_mut(&mut self, fields: &mut syn::FieldsUnnamed) {
+        fields.unnamed = std::mem::take(&mut fields.unnamed)
+            .into_pairs()
+            .filter(|p| self.0.is_enabled(&p.value().attrs))
+            .collect();
+        visit_mut::visit_fields_unnamed_mut(self, fields);
+    }
+
+    fn visit_item_enum_mut(&mut self, e: &mut syn::ItemEnum) {
+        e.variants = std::mem::take(&mut e.variants)
+            .into_pairs()
+            .filter(|p| self.0.is_enabled(&p.value().attrs))
+            .collect();
+        visit_mut::visit_item_enum_mut(self, e);
+    }
+
+    fn visit_expr_match_mut(&mut self, m: &mut syn::ExprMatch) {
+        m.arms.retain(|arm: &Arm| self.0.is_enabled(&arm.attrs));
+        visit_mut::visit_expr_match_mut(self, m);
+    }
+}
+
+/// Attributes of expressions that commonly appear in statement position
+fn expr_attrs(expr: &Expr) -> &[Attribute] {
+    match expr {
+        Expr::Assign(e) => &e.attrs,
+        Expr::AssignOp(e) => &e.attrs,
+        Expr::Block(e) => &e.attrs,
+        Expr::Call(e) => &e.attrs,
+        Expr::ForLoop(e) => &e.attrs,
+        Expr::If(e) => &e.attrs,
+        Expr::Loop(e) => &e.attrs,
+        Expr::Macro(e) => &e.attrs,
+        Expr::Match(e) => &e.attrs,
+        Expr::MethodCall(e) => &e.attrs,
+        Expr::Unsafe(e) => &e.attrs,
+        Expr::While(e) => &e.attrs,
+        _ => &[],
+    }
+}
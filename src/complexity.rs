@@ -0,0 +1,78 @@
+//! Cyclomatic-style complexity of functions
+
+use syn::{
+    visit::{self, Visit},
+    BinOp, Block, ExprBinary, ExprForLoop, ExprIf, ExprLoop, ExprMatch,
+    ExprWhile, File, ImplItem, Item,
+};
+
+use crate::type_name;
+
+/// Complexity of each top-level function and method in impls, like
+/// `("Parser::parse", 14)`: one, plus one for each `if`, match arm, loop,
+/// `&&` and `||`. Items nested in the bodies don't count.
+pub fn complexity(file: &File) -> Vec<(String, usize)> {
+    let of = |block: &Block| {
+        let mut count = Count(1);
+        count.visit_block(block);
+        count.0
+    };
+    let mut counts = vec![];
+    for item in &file.items {
+        match item {
+            Item::Fn(f) => counts.push((f.sig.ident.to_string(), of(&f.block))),
+            Item::Impl(i) => {
+                let ty = match type_name(&i.self_ty) {
+                    Some(ty) => ty,
+                    None => continue,
+                };
+                for member in &i.items {
+                    if let ImplItem::Method(m) = member {
+                        let name = format!("{}::{}", ty, m.sig.ident);
+                        counts.push((name, of(&m.block)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+struct Count(usize);
+
+impl<'ast> Visit<'ast> for Count {
+    fn visit_expr_if(&mut self, i: &'ast ExprIf) {
+        self.0 += 1;
+        visit::visit_expr_if(self, i);
+    }
+
+    fn visit_expr_match(&mut self, i: &'ast ExprMatch) {
+        self.0 += i.arms.len();
+        visit::visit_expr_match(self, i);
+    }
+
+    fn visit_expr_while(&mut self, i: &'ast ExprWhile) {
+        self.0 += 1;
+        visit::visit_expr_while(self, i);
+    }
+
+    fn visit_expr_for_loop(&mut self, i: &'ast ExprForLoop) {
+        self.0 += 1;
+        visit::visit_expr_for_loop(self, i);
+    }
+
+    fn visit_expr_loop(&mut self, i: &'ast ExprLoop) {
+        self.0 += 1;
+        visit::visit_expr_loop(self, i);
+    }
+
+    fn visit_expr_binary(&mut self, i: &'ast ExprBinary) {
+        if matches!(i.op, BinOp::And(_) | BinOp::Or(_)) {
+            self.0 += 1;
+        }
+        visit::visit_expr_binary(self, i);
+    }
+
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
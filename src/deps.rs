@@ -0,0 +1,272 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+};
+
+use clap::ValueEnum;
+
+use syn::{
+    punctuated::Punctuated,
+    visit::{self, Visit},
+    File, Ident, ImplItem, Item, ItemUse, Path, UseGroup, UsePath, UseTree,
+};
+
+use crate::{error::Result, item_info, type_name};
+
+/// Appends the file's top-level items that `items` refer to by name,
+/// transitively, in source order. Names already seen are skipped, so
+/// recursive and mutually recursive types terminate.
+pub fn with_deps(file: &File, mut items: Vec<Item>) -> Vec<Item> {
+    let mut seen: HashSet<String> = items
+        .iter()
+        .filter_map(|item| Some(item_info(item)?.1.to_string()))
+        .collect();
+    // indices into the file, so the deps come out in source order
+    let mut found = BTreeSet::new();
+    let mut queue: Vec<&Item> = items.iter().collect();
+    while let Some(item) = queue.pop() {
+        let mut refs = Refs::default();
+        refs.visit_item(item);
+        for name in refs.names {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            for (i, dep) in file.items.iter().enumerate() {
+                if item_info(dep).is_some_and(|(_, n)| *n == name) {
+                    found.insert(i);
+                    queue.push(dep);
+                }
+            }
+        }
+    }
+    items.extend(found.into_iter().map(|i| file.items[i].clone()));
+    items
+}
+
+/// The file's top-level items other than `name` itself that refer to it
+pub fn users_of<'a>(file: &'a File, name: &str) -> Vec<&'a Item> {
+    let users = file.items.iter().filter(|item| {
+        let mut refs = Refs::default();
+        refs.visit_item(item);
+        refs.names.iter().any(|n| n == name)
+            && item_info(item).is_none_or(|(_, n)| n != name)
+    });
+    users.collect()
+}
+
+/// Counts, for each top-level function and method in impls, how many of the
+/// file's other top-level items it refers to, like `("Parser::parse", 7)`
+pub fn coupling(file: &File) -> Vec<(String, usize)> {
+    let local: HashSet<_> = file
+        .items
+        .iter()
+        .filter_map(|item| Some(item_info(item)?.1.to_string()))
+        .collect();
+    // the item itself, or the type of a method, doesn't count
+    let count = |own: &Ident, refs: Refs| {
+        let deps: BTreeSet<_> = refs
+            .names
+            .iter()
+            .filter(|n| local.contains(*n) && *own != n)
+            .collect();
+        deps.len()
+    };
+    let mut counts = vec![];
+    for item in &file.items {
+        match item {
+            Item::Fn(f) => {
+                let mut refs = Refs::default();
+                refs.visit_item_fn(f);
+                let deps = count(&f.sig.ident, refs);
+                counts.push((f.sig.ident.to_string(), deps));
+            }
+            Item::Impl(i) => {
+                let ty = match type_name(&i.self_ty) {
+                    Some(ty) => ty,
+                    None => continue,
+                };
+                for member in &i.items {
+                    if let ImplItem::Method(m) = member {
+                        let mut refs = Refs::default();
+                        refs.visit_impl_item_method(m);
+                        let name = format!("{}::{}", ty, m.sig.ident);
+                        counts.push((name, count(ty, refs)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// The parts of the file's top-level `use` declarations that import names
+/// `items` refer to. Glob imports are left out, as what they import isn't
+/// known.
+pub fn context_uses(file: &File, items: &[Item]) -> Vec<Item> {
+    let mut refs = Refs::default();
+    for item in items {
+        refs.visit_item(item);
+    }
+    let names: HashSet<_> = refs.names.into_iter().collect();
+    let uses = file.items.iter().filter_map(|item| match item {
+        Item::Use(u) => {
+            let tree = prune(&u.tree, None, &names)?;
+            Some(Item::Use(ItemUse { tree, ..u.clone() }))
+        }
+        _ => None,
+    });
+    uses.collect()
+}
+
+/// Keeps the parts of a `use` tree importing one of `names`. `parent` is the
+/// last segment before the tree, which `self` imports.
+fn prune(
+    tree: &UseTree,
+    parent: Option<&Ident>,
+    names: &HashSet<String>,
+) -> Option<UseTree> {
+    let used = |ident: &Ident| names.contains(&ident.to_string());
+    match tree {
+        UseTree::Path(p) => {
+            let inner = prune(&p.tree, Some(&p.ident), names)?;
+            Some(UseTree::Path(UsePath {
+                tree: Box::new(inner),
+                ..p.clone()
+            }))
+        }
+        UseTree::Name(n) if n.ident == "self" => {
+            parent.filter(|p| used(p)).map(|_| tree.clone())
+        }
+        UseTree::Name(n) => used(&n.ident).then(|| tree.clone()),
+        UseTree::Rename(r) => used(&r.rename).then(|| tree.clone()),
+        UseTree::Glob(_) => None,
+        UseTree::Group(g) => {
+            let items: Punctuated<_, _> = g
+                .items
+                .iter()
+                .filter_map(|tree| prune(tree, parent, names))
+                .collect();
+            match items.len() {
+                0 => None,
+                _ => Some(UseTree::Group(UseGroup { items, ..g.clone() })),
+            }
+        }
+    }
+}
+
+/// Which references `graph` draws edges for
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Edges {
+    /// Functions referring to functions
+    Calls,
+    /// Items referring to types, consts and other non-function items
+    Types,
+    Both,
+}
+
+/// Prints a Graphviz graph of the references between the file's top-level
+/// items, with functions as ellipses and other items as boxes. Type
+/// references are dashed.
+pub fn graph(file: &File, edges: Edges, out: &mut dyn Write) -> Result<()> {
+    let named: Vec<_> = file
+        .items
+        .iter()
+        .filter_map(|item| Some((item, item_info(item)?.1.to_string())))
+        .collect();
+    let is_fn: HashMap<_, _> = named
+        .iter()
+        .map(|(item, name)| (name.as_str(), matches!(item, Item::Fn(_))))
+        .collect();
+    writeln!(out, "digraph deps {{")?;
+    for (item, name) in &named {
+        let shape = match item {
+            Item::Fn(_) => "ellipse",
+            _ => "box",
+        };
+        writeln!(out, "    {:?} [shape={}];", name, shape)?;
+    }
+    for (item, name) in &named {
+        let mut refs = Refs::default();
+        refs.visit_item(item);
+        let targets: BTreeSet<_> = refs.names.into_iter().collect();
+        for target in targets.iter().filter(|t| *t != name) {
+            let call = match is_fn.get(target.as_str()) {
+                Some(to_fn) => matches!(item, Item::Fn(_)) && *to_fn,
+                None => continue,
+            };
+            match (call, edges) {
+                (true, Edges::Calls | Edges::Both) => {
+                    writeln!(out, "    {:?} -> {:?};", name, target)?
+                }
+                (false, Edges::Types | Edges::Both) => writeln!(
+                    out,
+                    "    {:?} -> {:?} [style=dashed];",
+                    name, target
+                )?,
+                _ => {}
+            }
+        }
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+/// Collects the identifiers of all path segments, which covers types,
+/// function calls and constants
+#[derive(Default)]
+struct Refs {
+    names: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for Refs {
+    fn visit_path(&mut self, path: &'ast Path) {
+        for segment in &path.segments {
+            self.names.push(segment.ident.to_string());
+        }
+        visit::visit_path(self, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn names(items: &[Item]) -> Vec<String> {
+        let names = items.iter().filter_map(item_info);
+        names.map(|(_, name)| name.to_string()).collect()
+    }
+
+    #[test]
+    fn recursive_struct() {
+        let file: File = parse_quote! {
+            struct Node {
+                next: Option<Box<Node>>,
+            }
+            struct Unused;
+        };
+        let items = with_deps(&file, vec![file.items[0].clone()]);
+        assert_eq!(names(&items), ["Node"]);
+    }
+
+    #[test]
+    fn mutually_recursive_enums() {
+        let file: File = parse_quote! {
+            struct Unused;
+            enum Expr {
+                Block(Vec<Stmt>),
+                Lit(u8),
+            }
+            enum Stmt {
+                Expr(Box<Expr>),
+                Empty,
+            }
+        };
+        let items = with_deps(&file, vec![file.items[1].clone()]);
+        assert_eq!(names(&items), ["Expr", "Stmt"]);
+        let items = with_deps(&file, vec![file.items[2].clone()]);
+        assert_eq!(names(&items), ["Stmt", "Expr"]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Line diffs of extracted items, highlighting the changed words
+
+use std::io::Write;
+
+use owo_colors::{OwoColorize, Stream::Stdout, Style};
+use similar::{ChangeTag, TextDiff};
+
+use crate::error::Result;
+
+/// Prints a diff of the whole of `old` and `new`, with the changed parts of
+/// changed lines underlined
+pub fn print(
+    old: (&str, &str),
+    new: (&str, &str),
+    out: &mut dyn Write,
+) -> Result<()> {
+    let ((old_label, old), (new_label, new)) = (old, new);
+    writeln!(out, "--- {}", old_label)?;
+    writeln!(out, "+++ {}", new_label)?;
+    let diff = TextDiff::from_lines(old, new);
+    for op in diff.ops() {
+        for change in diff.iter_inline_changes(op) {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ("-", Style::new().red()),
+                ChangeTag::Insert => ("+", Style::new().green()),
+                ChangeTag::Equal => (" ", Style::new()),
+            };
+            write!(
+                out,
+                "{}",
+                sign.if_supports_color(Stdout, |s| s.style(style))
+            )?;
+            for (emphasized, text) in change.iter_strings_lossy() {
+                let style = match emphasized {
+                    true => style.underline().bold(),
+                    false => style,
+                };
+                // keep the newline out of the escape codes
+                let (text, newline) = match text.strip_suffix('\n') {
+                    Some(text) => (text, "\n"),
+                    None => (&*text, ""),
+                };
+                write!(
+                    out,
+                    "{}{}",
+                    text.if_supports_color(Stdout, |t| t.style(style)),
+                    newline
+                )?;
+            }
+            if change.missing_newline() {
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
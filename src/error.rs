@@ -0,0 +1,135 @@
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub const EXIT_NOT_FOUND: i32 = 1;
+pub const EXIT_PARSE: i32 = 2;
+pub const EXIT_IO: i32 = 3;
+pub const EXIT_USAGE: i32 = 4;
+pub const EXIT_CHECK: i32 = 5;
+
+pub enum Error {
+    /// Things that were looked for but don't exist, like "`foo`"
+    NotFound(Vec<String>),
+    Read(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// `git show` failed for `--since-git`
+    Git {
+        path: PathBuf,
+        rev: String,
+        message: String,
+    },
+    /// Reading a member of an archive input failed
+    Archive {
+        path: PathBuf,
+        member: String,
+        message: String,
+    },
+    Write(io::Error),
+    /// `cargo metadata` failed for a `--workspace` manifest
+    Metadata(PathBuf, cargo_metadata::Error),
+    /// Invalid arguments that clap couldn't catch
+    Usage(String),
+    /// Extracted items whose output didn't parse for `--verify`, with the
+    /// errors
+    Verify(Vec<String>),
+    /// A check like `list --undocumented --strict` found problems, which were
+    /// already printed
+    Check(String),
+    /// Some of several input files failed, and were already reported, with
+    /// the exit code of the first failure
+    Files(usize, i32),
+}
+
+impl Error {
+    pub fn not_found(what: impl Into<String>) -> Self {
+        Error::NotFound(vec![what.into()])
+    }
+
+    /// Exit code for scripts to tell failures apart, listed in `--help`
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NotFound(_) => EXIT_NOT_FOUND,
+            // output that doesn't parse is a parse error too
+            Error::Parse { .. } | Error::Verify(_) => EXIT_PARSE,
+            Error::Read(..)
+            | Error::Git { .. }
+            | Error::Archive { .. }
+            | Error::Write(_)
+            | Error::Metadata(..) => EXIT_IO,
+            Error::Usage(_) => EXIT_USAGE,
+            Error::Check(_) => EXIT_CHECK,
+            Error::Files(_, code) => *code,
+        }
+    }
+
+    pub fn parse(path: PathBuf, error: syn::Error) -> Self {
+        let pos = error.span().start();
+        Error::Parse {
+            path,
+            line: pos.line,
+            column: pos.column + 1,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Write(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(what) => write!(f, "{} not found", what.join(", ")),
+            Error::Read(path, e) => {
+                write!(f, "couldn't read {}: {}", path.display(), e)
+            }
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                write!(f, "{}:{}:{}: {}", path.display(), line, column, message)
+            }
+            Error::Git { path, rev, message } => {
+                write!(
+                    f,
+                    "couldn't read {} at {}: {}",
+                    path.display(),
+                    rev,
+                    message
+                )
+            }
+            Error::Archive {
+                path,
+                member,
+                message,
+            } => write!(
+                f,
+                "couldn't read {} from {}: {}",
+                member,
+                path.display(),
+                message
+            ),
+            Error::Write(e) => write!(f, "couldn't write output: {}", e),
+            Error::Metadata(path, e) => {
+                write!(f, "couldn't load workspace {}: {}", path.display(), e)
+            }
+            Error::Usage(msg) => f.write_str(msg),
+            Error::Verify(errors) => {
+                write!(f, "output doesn't parse: {}", errors.join("; "))
+            }
+            Error::Check(msg) => f.write_str(msg),
+            Error::Files(n, _) => write!(f, "{} of the input files failed", n),
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! Textual post-processing of unparsed code
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Where a scan through the code is, as far as literals and comments go
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Code,
+    Str,
+    /// Raw string closed by `"` and this many `#`s
+    RawStr(usize),
+    /// Block comment with this nesting depth
+    Comment(usize),
+}
+
+/// Scans `line` starting in `state`, calling `f` with the byte index of each
+/// character that is code, i.e. not inside a literal or comment. Returns the
+/// state at the end of the line.
+pub fn scan(
+    line: &str,
+    mut state: State,
+    mut f: impl FnMut(usize, char),
+) -> State {
+    let bytes = line.as_bytes();
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let rest = &line[i..];
+        match state {
+            State::Code => match c {
+                '/' if rest.starts_with("//") => return State::Code,
+                '/' if rest.starts_with("/*") => {
+                    chars.next();
+                    state = State::Comment(1);
+                }
+                '"' => state = State::Str,
+                'r' if is_raw_string_start(rest)
+                    && !(i > 0 && is_ident_byte(bytes[i - 1])) =>
+                {
+                    let hashes = rest[1..].bytes().take_while(|&b| b == b'#');
+                    let hashes = hashes.count();
+                    for _ in 0..hashes + 1 {
+                        chars.next();
+                    }
+                    state = State::RawStr(hashes);
+                }
+                '\'' => match char_literal_len(rest) {
+                    // skip over the literal, the closing quote included
+                    Some(len) => {
+                        while chars.peek().is_some_and(|&(j, _)| j < i + len) {
+                            chars.next();
+                        }
+                    }
+                    None => f(i, c),
+                },
+                _ => f(i, c),
+            },
+            State::Str => match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => state = State::Code,
+                _ => {}
+            },
+            State::RawStr(hashes) => {
+                if c == '"'
+                    && rest[1..].bytes().take_while(|&b| b == b'#').count()
+                        >= hashes
+                {
+                    for _ in 0..hashes {
+                        chars.next();
+                    }
+                    state = State::Code;
+                }
+            }
+            State::Comment(depth) => {
+                if rest.starts_with("*/") {
+                    chars.next();
+                    state = match depth {
+                        1 => State::Code,
+                        _ => State::Comment(depth - 1),
+                    };
+                } else if rest.starts_with("/*") {
+                    chars.next();
+                    state = State::Comment(depth + 1);
+                }
+            }
+        }
+    }
+    state
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Whether `s` starts with `r"` or `r#...#"`
+fn is_raw_string_start(s: &str) -> bool {
+    s[1..].trim_start_matches('#').starts_with('"')
+}
+
+/// Length in bytes of the char literal `s` starts with, if it's not a
+/// lifetime
+fn char_literal_len(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices().skip(1);
+    match chars.next()? {
+        (_, '\\') => {
+            let (end, _) = chars.find(|&(_, c)| c == '\'')?;
+            Some(end + 1)
+        }
+        (_, _) => match chars.next()? {
+            (end, '\'') => Some(end + 1),
+            _ => None,
+        },
+    }
+}
+
+/// For each line of `text`, whether it starts inside a multi-line literal or
+/// comment, and so mustn't be touched
+pub fn continued_lines(text: &str) -> Vec<bool> {
+    let mut state = State::Code;
+    text.lines()
+        .map(|line| {
+            let continued = state != State::Code;
+            state = scan(line, state, |_, _| {});
+            continued
+        })
+        .collect()
+}
+
+/// Prefixes the lines of `text` with `indent`, except for empty ones and
+/// those inside multi-line literals and comments
+pub fn indent(text: &str, indent: &str) -> String {
+    let mut out = String::new();
+    for (line, continued) in text.lines().zip(continued_lines(text)) {
+        if !(continued || line.is_empty()) {
+            out += indent;
+        }
+        out += line;
+        out.push('\n');
+    }
+    out
+}
+
+/// Replaces each level of the 4-space indentation prettyplease uses with
+/// `indent`, leaving the lines inside multi-line literals and comments alone
+pub fn reindent(text: &str, indent: &str) -> String {
+    let mut out = String::new();
+    for (line, continued) in text.lines().zip(continued_lines(text)) {
+        let code = line.trim_start_matches(' ');
+        if !continued {
+            let spaces = line.len() - code.len();
+            out += &indent.repeat(spaces / 4);
+            out += &" ".repeat(spaces % 4);
+            out += code;
+        } else {
+            out += line;
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Breaks lines longer than `width` by putting the elements of their
+/// bracketed, comma-separated lists on lines of their own
+pub fn reflow(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    for (line, continued) in text.lines().zip(continued_lines(text)) {
+        if continued {
+            out += line;
+            out.push('\n');
+        } else {
+            reflow_line(line, width, &mut out);
+        }
+    }
+    out
+}
+
+fn reflow_line(line: &str, width: usize, out: &mut String) {
+    if line.chars().count() <= width {
+        *out += line;
+        out.push('\n');
+        return;
+    }
+    let indent = &line[..line.len() - line.trim_start().len()];
+    if let Some((first, rest)) = split_bounds(line) {
+        reflow_line(first, width, out);
+        for bound in rest {
+            reflow_line(&format!("{}    + {}", indent, bound), width, out);
+        }
+        return;
+    }
+    let split = split_list(line);
+    let Some((head, elems, tail)) = split else {
+        *out += line;
+        out.push('\n');
+        return;
+    };
+    reflow_line(head, width, out);
+    for elem in elems {
+        reflow_line(&format!("{}    {},", indent, elem), width, out);
+    }
+    reflow_line(&format!("{}{}", indent, tail), width, out);
+}
+
+/// Splits a where clause predicate or generic parameter on its own line,
+/// like `T: Clone + Send,`, into the line up to the first bound and the
+/// other bounds, the last one keeping the comma
+fn split_bounds(line: &str) -> Option<(&str, Vec<&str>)> {
+    let trimmed = line.trim();
+    let (bounded, _) = trimmed.split_once(": ")?;
+    if bounded.contains(char::is_whitespace) || !trimmed.ends_with(',') {
+        return None;
+    }
+    let bytes = line.as_bytes();
+    let mut depth = 0;
+    let mut pluses = vec![];
+    scan(line, State::Code, |i, c| {
+        let prev = i.checked_sub(1).map(|i| bytes[i]);
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            '<' if prev.is_some_and(|b| is_ident_byte(b) || b == b':') => {
+                depth += 1
+            }
+            '>' if matches!(prev, Some(b'-' | b'=')) => {}
+            ')' | ']' | '}' | '>' if depth > 0 => depth -= 1,
+            '+' if depth == 0 && prev == Some(b' ') => pluses.push(i),
+            _ => {}
+        }
+    });
+    let first = pluses.first()?;
+    let mut rest = vec![];
+    for (i, plus) in pluses.iter().enumerate() {
+        let end = pluses.get(i + 1).map_or(line.len(), |next| *next);
+        rest.push(line[plus + 1..end].trim());
+    }
+    Some((line[..*first].trim_end(), rest))
+}
+
+/// Finds the first top-level bracketed list in `line` that contains commas,
+/// returning the line up to and including the opening bracket, the trimmed
+/// elements, and the rest of the line starting at the closing bracket.
+/// Generic arguments are skipped over like brackets, but never split.
+fn split_list(line: &str) -> Option<(&str, Vec<&str>, &str)> {
+    let bytes = line.as_bytes();
+    // open brackets, with `<` for generics
+    let mut stack = vec![];
+    let mut open = None;
+    let mut commas = vec![];
+    let mut found = None;
+    let state = scan(line, State::Code, |i, c| {
+        if found.is_some() {
+            return;
+        }
+        let prev = i.checked_sub(1).map(|i| bytes[i]);
+        match c {
+            '(' | '[' | '{' => {
+                if stack.is_empty() {
+                    open = Some(i);
+                    commas.clear();
+                }
+                stack.push(c);
+            }
+            // generics follow a name or `::` directly, comparisons and
+            // shifts get spaces
+            '<' if prev.is_some_and(|b| is_ident_byte(b) || b == b':') => {
+                if stack.is_empty() {
+                    open = None;
+                }
+                stack.push(c);
+            }
+            // not `->` or `=>`
+            '>' if stack.last() == Some(&'<')
+                && !matches!(prev, Some(b'-' | b'=')) =>
+            {
+                stack.pop();
+            }
+            ')' | ']' | '}' if !stack.is_empty() => {
+                stack.pop();
+                if stack.is_empty() && !commas.is_empty() {
+                    found = open.map(|open| (open, i));
+                }
+            }
+            ',' if stack.len() == 1 && stack[0] != '<' => commas.push(i),
+            _ => {}
+        }
+    });
+    let (open, close) = found?;
+    // don't split lines that continue into a literal or comment
+    if state != State::Code {
+        return None;
+    }
+    let mut elems = vec![];
+    let mut start = open + 1;
+    for comma in commas.into_iter().chain([close]) {
+        let elem = line[start..comma].trim();
+        if !elem.is_empty() {
+            elems.push(elem);
+        }
+        start = comma + 1;
+    }
+    Some((&line[..open + 1], elems, &line[close..]))
+}
+
+/// Squeezes code onto one line with as few spaces as possible. Works on
+/// tokens rather than text, so literals are kept exactly as written and
+/// whitespace inside strings is never touched. Comments are dropped, doc
+/// comments turn into `#[doc]` attributes.
+pub fn compact(text: &str) -> String {
+    match text.parse::<TokenStream>() {
+        Ok(tokens) => {
+            let mut out = String::new();
+            compact_tokens(tokens, &mut out);
+            out + "\n"
+        }
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Appends `tokens` to `out`, with spaces only where tokens would merge:
+/// between words, and between puncts that aren't joined
+fn compact_tokens(tokens: TokenStream, out: &mut String) {
+    // whether the last token was a word or a punct that isn't joined to the
+    // next one
+    let mut last: Option<bool> = None;
+    for token in tokens {
+        let is_word =
+            matches!(token, TokenTree::Ident(_) | TokenTree::Literal(_));
+        let is_punct = matches!(token, TokenTree::Punct(_));
+        if (is_word && last == Some(true)) || (is_punct && last == Some(false))
+        {
+            out.push(' ');
+        }
+        last = None;
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                out.push_str(open);
+                compact_tokens(group.stream(), out);
+                out.push_str(close);
+            }
+            TokenTree::Punct(punct) => {
+                out.push(punct.as_char());
+                if punct.spacing() == Spacing::Alone {
+                    last = Some(false);
+                }
+            }
+            token => {
+                out.push_str(&token.to_string());
+                last = Some(true);
+            }
+        }
+    }
+}
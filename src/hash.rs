@@ -0,0 +1,113 @@
+//! Content hashes of items, for telling which ones changed between versions
+
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use quote::ToTokens;
+use syn::{File, Item, ItemMacro, ItemUse, Visibility};
+
+use crate::{
+    error::{Error, Result},
+    item_label, Unparse,
+};
+
+/// Hashes of the file's top-level items by label. Labels that occur more
+/// than once, like those of several `impl Foo` blocks, get ` #2`, ` #3`, ...
+/// appended.
+fn hashes(file: &File) -> BTreeMap<String, u64> {
+    let mut hashes = BTreeMap::new();
+    let mut seen = BTreeMap::new();
+    for item in &file.items {
+        let label = label(item);
+        let n = seen.entry(label.clone()).or_insert(0);
+        *n += 1;
+        let key = match *n {
+            1 => label,
+            n => format!("{} #{}", label, n),
+        };
+        hashes.insert(key, fnv1a(&item.to_token_stream().to_string()));
+    }
+    hashes
+}
+
+/// Labels `use` declarations by their tree, like `use std::io::{self, Write}`,
+/// and modules and macros by their name, so adding one doesn't relabel the
+/// others
+fn label(item: &Item) -> String {
+    match item {
+        Item::Use(u) => {
+            let tree = ItemUse {
+                attrs: vec![],
+                vis: Visibility::Inherited,
+                ..u.clone()
+            };
+            let text = Item::Use(tree).unparse();
+            text.trim_end().trim_end_matches(';').to_string()
+        }
+        Item::Mod(m) => format!("mod {}", m.ident),
+        Item::Macro(ItemMacro {
+            ident: Some(name), ..
+        }) => {
+            format!("macro_rules! {}", name)
+        }
+        _ => item_label(item),
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the std hashers is the same everywhere
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Prints `hash<TAB>label` lines, the format `--changed-since` reads
+pub fn print(file: &File, out: &mut dyn Write) -> Result<()> {
+    for (label, hash) in hashes(file) {
+        writeln!(out, "{:016x}\t{}", hash, label)?;
+    }
+    Ok(())
+}
+
+/// Compares the items with the hashes stored in `path`, printing the items
+/// that were added, removed or changed since
+pub fn changed_since(
+    file: &File,
+    path: &Path,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let stored = fs::read_to_string(path)
+        .map_err(|e| Error::Read(path.to_owned(), e))?;
+    let mut old = BTreeMap::new();
+    for line in stored.lines().filter(|line| !line.trim().is_empty()) {
+        let parsed = line.split_once('\t').and_then(|(hash, label)| {
+            Some((u64::from_str_radix(hash, 16).ok()?, label))
+        });
+        let Some((hash, label)) = parsed else {
+            return Err(Error::Usage(format!(
+                "{} isn't a hash file, bad line `{}`",
+                path.display(),
+                line
+            )));
+        };
+        old.insert(label.to_string(), hash);
+    }
+    let new = hashes(file);
+    let mut changes = 0;
+    for (label, hash) in &new {
+        let status = match old.get(label) {
+            None => "added",
+            Some(old) if old != hash => "changed",
+            Some(_) => continue,
+        };
+        writeln!(out, "{:>8} {}", status, label)?;
+        changes += 1;
+    }
+    for label in old.keys().filter(|label| !new.contains_key(*label)) {
+        writeln!(out, "{:>8} {}", "removed", label)?;
+        changes += 1;
+    }
+    if changes == 0 {
+        writeln!(out, "No items changed")?;
+    }
+    Ok(())
+}
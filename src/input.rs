@@ -0,0 +1,231 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use cargo_metadata::MetadataCommand;
+use rayon::{prelude::*, ThreadPoolBuilder};
+
+use crate::error::{Error, Result};
+
+/// Expands directories in `paths` into the `.rs` files they contain,
+/// recursively and sorted by path. Hidden directories and `target` are
+/// skipped.
+pub fn collect(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            walk(path.clone(), &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// Finds the `.rs` files in the `src` directories of all workspace members
+/// of the given manifests, or directories containing a `Cargo.toml`, paired
+/// with the name of their crate
+pub fn collect_workspace(paths: &[PathBuf]) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = vec![];
+    for path in paths {
+        let manifest = match path.is_dir() {
+            true => path.join("Cargo.toml"),
+            false => path.clone(),
+        };
+        let metadata = MetadataCommand::new()
+            .manifest_path(&manifest)
+            .no_deps()
+            .exec()
+            .map_err(|e| Error::Metadata(manifest, e))?;
+        for package in metadata.workspace_packages() {
+            let src = package.manifest_path.with_file_name("src");
+            if !src.is_dir() {
+                continue;
+            }
+            let mut crate_files = vec![];
+            walk(src.into_std_path_buf(), &mut crate_files)?;
+            let name = &package.name;
+            files.extend(crate_files.into_iter().map(|f| (f, name.clone())));
+        }
+    }
+    Ok(files)
+}
+
+fn walk(dir: PathBuf, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(&dir).map_err(|e| Error::Read(dir.clone(), e));
+    let mut entries = entries?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Read(dir, e))?;
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                walk(path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads `path` as it was at git revision `rev`, using the repository the
+/// file is in
+pub fn git_show(path: &Path, rev: &str) -> Result<String> {
+    let error = |message: String| Error::Git {
+        path: path.to_owned(),
+        rev: rev.to_string(),
+        message,
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{}:./{}", rev, name))
+        .output()
+        .map_err(|e| error(format!("couldn't run git: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(error(stderr.trim().to_string()));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| error("file isn't valid UTF-8".to_string()))
+}
+
+/// Splits inputs like `foo-0.1.0.crate:src/lib.rs` into the archive and the
+/// member to read from it, for paths whose part before the `:` names a tar,
+/// gzipped tar or zip archive
+pub fn split_archive(path: &Path) -> Option<(&Path, &str)> {
+    let (archive, member) = path.to_str()?.rsplit_once(':')?;
+    let is_archive = [".tar", ".tar.gz", ".tgz", ".crate", ".zip"]
+        .iter()
+        .any(|ext| archive.ends_with(ext));
+    is_archive.then(|| (Path::new(archive), member))
+}
+
+/// Reads a member of an archive. In `.crate` files and other archives with
+/// all files in one directory, the member can be named without it, like
+/// `src/lib.rs`.
+#[cfg(feature = "archive")]
+pub fn read_archive(archive: &Path, member: &str) -> Result<String> {
+    use std::io::Read;
+
+    let error = |message: String| Error::Archive {
+        path: archive.to_owned(),
+        member: member.to_string(),
+        message,
+    };
+    let file =
+        fs::File::open(archive).map_err(|e| Error::Read(archive.into(), e))?;
+    let matches = |name: &str| {
+        name == member
+            || name.split_once('/').is_some_and(|(_, rest)| rest == member)
+    };
+    let mut bytes = vec![];
+    let name = archive.to_string_lossy();
+    let found = if name.ends_with(".zip") {
+        let mut zip =
+            zip::ZipArchive::new(file).map_err(|e| error(e.to_string()))?;
+        let names: Vec<_> = zip.file_names().map(String::from).collect();
+        match names.into_iter().find(|n| matches(n)) {
+            Some(name) => {
+                let mut entry =
+                    zip.by_name(&name).map_err(|e| error(e.to_string()))?;
+                entry.read_to_end(&mut bytes)
+            }
+            None => return Err(error("no such member".to_string())),
+        }
+    } else {
+        let reader: Box<dyn Read> = match name.ends_with(".tar") {
+            true => Box::new(file),
+            false => Box::new(flate2::read::GzDecoder::new(file)),
+        };
+        let mut tar = tar::Archive::new(reader);
+        let entries = tar.entries().map_err(|e| error(e.to_string()))?;
+        let mut found = None;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| error(e.to_string()))?;
+            let path = entry.path().map_err(|e| error(e.to_string()))?;
+            if matches(&path.to_string_lossy()) {
+                found = Some(entry.read_to_end(&mut bytes));
+                break;
+            }
+        }
+        found.ok_or_else(|| error("no such member".to_string()))?
+    };
+    found.map_err(|e| error(e.to_string()))?;
+    String::from_utf8(bytes)
+        .map_err(|_| error("member isn't valid UTF-8".to_string()))
+}
+
+#[cfg(not(feature = "archive"))]
+pub fn read_archive(archive: &Path, member: &str) -> Result<String> {
+    Err(Error::Archive {
+        path: archive.to_owned(),
+        member: member.to_string(),
+        message: "reading archives needs the `archive` feature".to_string(),
+    })
+}
+
+/// Runs `f` over all files on a thread pool with `jobs` threads (all CPUs if
+/// `None`), returning the results in the order of `files`
+pub fn map_files<R: Send>(
+    jobs: Option<usize>,
+    files: &[PathBuf],
+    f: impl Fn(&PathBuf) -> R + Sync,
+) -> Vec<R> {
+    let progress = progress_bar(files.len() as u64);
+    let f = |path: &PathBuf| {
+        let result = f(path);
+        progress.inc(1);
+        result
+    };
+    let results = if jobs == Some(1) {
+        files.iter().map(f).collect()
+    } else {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(jobs.unwrap_or(0))
+            .build()
+            .expect("couldn't start worker threads");
+        pool.install(|| files.par_iter().map(&f).collect())
+    };
+    progress.finish_and_clear();
+    results
+}
+
+/// Progress bar on stderr counting parsed files, hidden if stderr isn't a
+/// terminal
+#[cfg(feature = "progress")]
+fn progress_bar(len: u64) -> indicatif::ProgressBar {
+    let style = indicatif::ProgressStyle::with_template(
+        "{bar:40} {pos}/{len} files {elapsed}",
+    )
+    .expect("valid template")
+    // plain ASCII, so it works with --ascii terminals too
+    .progress_chars("#>-");
+    indicatif::ProgressBar::new(len).with_style(style)
+}
+
+#[cfg(not(feature = "progress"))]
+fn progress_bar(_len: u64) -> NoProgress {
+    NoProgress
+}
+
+/// Stands in for the progress bar without the `progress` feature
+#[cfg(not(feature = "progress"))]
+struct NoProgress;
+
+#[cfg(not(feature = "progress"))]
+impl NoProgress {
+    fn inc(&self, _delta: u64) {}
+    fn finish_and_clear(&self) {}
+}
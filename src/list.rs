@@ -0,0 +1,332 @@
+use std::{cmp::Reverse, io::Write};
+
+use clap::Args;
+use owo_colors::{OwoColorize, Stream::Stdout, Style};
+use quote::ToTokens;
+use syn::{
+    spanned::Spanned, Attribute, File, ForeignItem, GenericParam, Generics,
+    Item, Visibility,
+};
+
+use crate::{
+    api, attr_is, complexity, deps,
+    error::{Error, Result},
+    foreign_info, impl_name, item_attrs, item_info, macro_calls, no_std,
+};
+
+#[derive(Args)]
+pub struct ListOpts {
+    /// Show the number of generic parameters of each item
+    #[clap(long)]
+    generics: bool,
+    /// Print `kind<TAB>name<TAB>line` rows without colors or a header
+    #[clap(long)]
+    tsv: bool,
+    /// Only list `static mut` items, to audit global mutable state
+    #[clap(long = "mut")]
+    mut_only: bool,
+    /// Only list items with const generic parameters, and show them
+    #[clap(long)]
+    const_generics: bool,
+    /// Only list the N items spanning the most lines, impl blocks included,
+    /// longest first, with their line ranges
+    #[clap(long, value_name = "N")]
+    longest: Option<usize>,
+    /// List the items using `std::` paths with the paths, for porting to
+    /// `no_std`
+    #[clap(long)]
+    no_std: bool,
+    /// List functions and methods by how many of the file's other items
+    /// they refer to, most first
+    #[clap(long)]
+    coupling: bool,
+    /// List functions and methods by complexity, counting branches, loops
+    /// and `&&`/`||`, most complex first
+    #[clap(long, conflicts_with = "coupling")]
+    complexity: bool,
+    /// List module-level macro invocations, like `lazy_static! { ... }`,
+    /// instead of items
+    #[clap(long)]
+    macro_calls: bool,
+    /// Only list public items without doc comments, for checking doc
+    /// coverage
+    #[clap(long)]
+    undocumented: bool,
+    /// With `--undocumented`, fail if any items are listed
+    #[clap(long, requires = "undocumented")]
+    strict: bool,
+    /// Print the public API reachable from the crate root as a tree of
+    /// modules with the signatures of their public items
+    #[clap(long, alias = "public-only-tree")]
+    api: bool,
+    /// Print the `--api` tree as JSON
+    #[clap(long, requires = "api")]
+    json: bool,
+}
+
+/// Style of the right-aligned item kind column
+pub fn kind_style() -> Style {
+    Style::new().green().bold()
+}
+
+/// A line of the listing
+struct Entry<'a> {
+    kind: &'static str,
+    name: String,
+    line: usize,
+    end_line: usize,
+    generics: Option<&'a Generics>,
+    mutable: bool,
+    constness: bool,
+    asyncness: bool,
+    public: bool,
+    attrs: &'a [Attribute],
+}
+
+/// Lists the items of the file, only ones with a `has_attr` attribute if
+/// given, and only `const fn`s with `const_fn` and `async fn`s with
+/// `async_fn`
+pub fn list(
+    file: &File,
+    opts: &ListOpts,
+    has_attr: Option<&str>,
+    const_fn: bool,
+    async_fn: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if opts.no_std {
+        return no_std::report(file, out);
+    }
+    if opts.api {
+        return api::tree(file, opts.json, out);
+    }
+    if opts.coupling {
+        let mut counts = deps::coupling(file);
+        counts.sort_by_key(|(_, deps)| Reverse(*deps));
+        for (name, deps) in counts {
+            let plural = if deps == 1 { "" } else { "s" };
+            writeln!(out, "fn {}: {} dep{}", name, deps, plural)?;
+        }
+        return Ok(());
+    }
+    if opts.macro_calls {
+        for (path, m) in macro_calls(file) {
+            writeln!(out, "{}! at line {}", path, m.span().start().line)?;
+        }
+        return Ok(());
+    }
+    if opts.complexity {
+        let mut counts = complexity::complexity(file);
+        counts.sort_by_key(|(_, n)| Reverse(*n));
+        for (name, n) in counts {
+            writeln!(out, "fn {}: complexity {}", name, n)?;
+        }
+        return Ok(());
+    }
+    let mut entries = entries(file, opts);
+    if let Some(name) = has_attr {
+        entries.retain(|e| e.attrs.iter().any(|attr| attr_is(attr, name)));
+    }
+    if const_fn {
+        entries.retain(|e| e.constness);
+    }
+    if async_fn {
+        entries.retain(|e| e.asyncness);
+    }
+    if opts.undocumented {
+        entries.retain(|e| {
+            e.public && !e.attrs.iter().any(|attr| attr.path.is_ident("doc"))
+        });
+    }
+    // after filtering, so there are N of the kinds asked for
+    if let Some(n) = opts.longest {
+        entries.sort_by_key(|e| Reverse(e.lines()));
+        entries.truncate(n);
+    }
+    // counted before the listing consumes the entries
+    let undocumented = match opts.strict {
+        true => entries.len(),
+        false => 0,
+    };
+    if opts.tsv {
+        for e in entries {
+            writeln!(out, "{}\t{}\t{}", e.kind, e.name, e.line)?;
+        }
+        return strict_check(undocumented);
+    }
+    writeln!(out, "Listing items:")?;
+    if entries.is_empty() {
+        writeln!(out, "No items found")?;
+    }
+    for e in entries {
+        let kind = e.kind.if_supports_color(Stdout, |k| k.style(kind_style()));
+        let name = e.name.if_supports_color(Stdout, |n| n.purple());
+        write!(out, "{:>12} {}", kind, name)?;
+        if e.asyncness {
+            write!(
+                out,
+                " {}",
+                "(async)".if_supports_color(Stdout, |a| a.cyan())
+            )?;
+        }
+        if opts.longest.is_some() {
+            let range =
+                format!("{}-{} ({} lines)", e.line, e.end_line, e.lines());
+            write!(
+                out,
+                " {}",
+                range.if_supports_color(Stdout, |r| r.dimmed())
+            )?;
+        }
+        let summary = match e.generics {
+            Some(g) if opts.const_generics => {
+                Some(format!("<{}>", const_params(g).join(", ")))
+            }
+            Some(g) if opts.generics && !g.params.is_empty() => {
+                Some(generics_summary(g))
+            }
+            _ => None,
+        };
+        match summary {
+            Some(s) => writeln!(
+                out,
+                " {}",
+                s.if_supports_color(Stdout, |s| s.dimmed())
+            )?,
+            None => writeln!(out)?,
+        }
+    }
+    strict_check(undocumented)
+}
+
+fn strict_check(undocumented: usize) -> Result<()> {
+    match undocumented {
+        0 => Ok(()),
+        1 => Err(Error::Check("1 public item is undocumented".to_string())),
+        n => Err(Error::Check(format!("{} public items are undocumented", n))),
+    }
+}
+
+fn is_pub(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+impl Entry<'_> {
+    fn lines(&self) -> usize {
+        self.end_line - self.line + 1
+    }
+}
+
+fn entries<'a>(file: &'a File, opts: &ListOpts) -> Vec<Entry<'a>> {
+    let mut entries = vec![];
+    for item in &file.items {
+        if let Item::ForeignMod(block) = item {
+            for foreign in &block.items {
+                if let Some((kind, name)) = foreign_info(foreign) {
+                    entries.push(Entry {
+                        kind,
+                        name: name.to_string(),
+                        line: foreign.span().start().line,
+                        end_line: foreign.span().end().line,
+                        generics: None,
+                        mutable: matches!(
+                            foreign,
+                            ForeignItem::Static(s) if s.mutability.is_some()
+                        ),
+                        constness: false,
+                        asyncness: false,
+                        public: match foreign {
+                            ForeignItem::Fn(i) => is_pub(&i.vis),
+                            ForeignItem::Static(i) => is_pub(&i.vis),
+                            ForeignItem::Type(i) => is_pub(&i.vis),
+                            _ => false,
+                        },
+                        attrs: match foreign {
+                            ForeignItem::Fn(i) => &i.attrs,
+                            ForeignItem::Static(i) => &i.attrs,
+                            ForeignItem::Type(i) => &i.attrs,
+                            _ => &[],
+                        },
+                    });
+                }
+            }
+            continue;
+        }
+        let (kind, name) = match (item_info(item), item) {
+            (Some((kind, name)), _) => (kind, name.to_string()),
+            // impls are often the longest items, the ones to split up
+            (None, Item::Impl(i))
+                if opts.generics
+                    || opts.const_generics
+                    || opts.longest.is_some() =>
+            {
+                ("impl", impl_name(i))
+            }
+            _ => continue,
+        };
+        let asyncness =
+            matches!(item, Item::Fn(f) if f.sig.asyncness.is_some());
+        entries.push(Entry {
+            kind,
+            name,
+            line: item.span().start().line,
+            end_line: item.span().end().line,
+            generics: generics(item),
+            mutable: matches!(item, Item::Static(s) if s.mutability.is_some()),
+            constness: matches!(item, Item::Fn(f) if f.sig.constness.is_some()),
+            asyncness,
+            public: api::visibility(item).is_some_and(is_pub),
+            attrs: item_attrs(item),
+        });
+    }
+    if opts.mut_only {
+        entries.retain(|e| e.mutable);
+    }
+    if opts.const_generics {
+        entries.retain(|e| {
+            e.generics.is_some_and(|g| !const_params(g).is_empty())
+        });
+    }
+    entries
+}
+
+pub fn generics(item: &Item) -> Option<&Generics> {
+    match item {
+        Item::Fn(i) => Some(&i.sig.generics),
+        Item::Struct(i) => Some(&i.generics),
+        Item::Enum(i) => Some(&i.generics),
+        Item::Trait(i) => Some(&i.generics),
+        Item::TraitAlias(i) => Some(&i.generics),
+        Item::Type(i) => Some(&i.generics),
+        Item::Union(i) => Some(&i.generics),
+        Item::Impl(i) => Some(&i.generics),
+        _ => None,
+    }
+}
+
+/// Renders parameter counts like `<2 type, 1 lt>`
+fn generics_summary(generics: &Generics) -> String {
+    let (mut types, mut lifetimes, mut consts) = (0, 0, 0);
+    for param in &generics.params {
+        match param {
+            GenericParam::Type(_) => types += 1,
+            GenericParam::Lifetime(_) => lifetimes += 1,
+            GenericParam::Const(_) => consts += 1,
+        }
+    }
+    let counts: Vec<_> =
+        [(types, "type"), (lifetimes, "lt"), (consts, "const")]
+            .into_iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, what)| format!("{} {}", n, what))
+            .collect();
+    format!("<{}>", counts.join(", "))
+}
+
+/// Renders const generic parameters like `const N: usize`
+pub fn const_params(generics: &Generics) -> Vec<String> {
+    generics
+        .const_params()
+        .map(|p| format!("const {}: {}", p.ident, p.ty.to_token_stream()))
+        .collect()
+}
@@ -1,152 +1,3027 @@
-use std::{fs, path::PathBuf};
+mod api;
+mod audit;
+mod cfg;
+mod complexity;
+mod deps;
+mod diff;
+mod error;
+mod format;
+mod hash;
+mod input;
+mod list;
+mod markdown;
+mod modules;
+mod no_std;
+mod signature;
+mod source;
+mod timing;
+mod transform;
 
-use clap::{Parser, Subcommand};
-use owo_colors::OwoColorize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, BufWriter, IsTerminal, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    time::Instant,
+};
+
+use clap::{Args, CommandFactory, ErrorKind, Parser, Subcommand};
+use clap_complete::Shell;
+use error::{Error, Result};
+use list::kind_style;
+use owo_colors::{
+    OwoColorize,
+    Stream::{Stderr, Stdout},
+    Style,
+};
 use prettyplease::unparse;
+use proc_macro2::{LineColumn, TokenStream};
+use quote::ToTokens;
+use source::Source;
 use syn::{
-    File, Item, ItemConst, ItemEnum, ItemExternCrate, ItemFn, ItemMacro,
-    ItemStatic, ItemStruct, ItemTrait, ItemType, ItemUnion,
+    parse_quote,
+    spanned::Spanned,
+    visit::{self, Visit},
+    Attribute, Expr, ExprCall, ExprLit, ExprStruct, ExprUnary, Fields, File,
+    FnArg, ForeignItem, GenericParam, Generics, Ident, ImplItem, Item,
+    ItemConst, ItemEnum, ItemExternCrate, ItemFn, ItemForeignMod, ItemImpl,
+    ItemMacro, ItemStatic, ItemStruct, ItemTrait, ItemTraitAlias, ItemType,
+    ItemUnion, Lit, Member, Meta, NestedMeta, Pat, ReturnType, Signature,
+    TraitItem, UnOp, Visibility, WherePredicate,
 };
 
-#[derive(Subcommand)]
-enum ExtractItem {
-    #[clap(name = "list")]
-    ListItems,
-    #[clap(alias = "f")]
-    Function {
-        name: String,
-    },
-    #[clap(alias = "s")]
-    Struct {
-        name: String,
-    },
-    #[clap(alias = "e")]
-    Enum {
-        name: String,
-    },
-    #[clap(alias = "t")]
-    Trait {
-        name: String,
-    },
-    #[clap(alias = "c")]
-    Const {
-        name: String,
-    },
-    ExternCrate {
-        name: String,
-    },
-    Static {
-        name: String,
-    },
-    Type {
-        name: String,
-    },
-    Union {
-        name: String,
-    },
-    /// Note: output might be mangled
-    Macro {
-        name: String,
-    },
+#[derive(Subcommand)]
+enum ExtractItem {
+    #[clap(name = "list")]
+    ListItems(list::ListOpts),
+    /// List the item kinds with their subcommands and aliases
+    Kinds,
+    /// Report top-level items that share a name
+    LintDups,
+    /// Report every `unsafe` fn, block, impl and trait, and their count
+    AuditUnsafe {
+        /// Also report the items using `std::` paths, for porting to
+        /// `no_std`
+        #[clap(long)]
+        no_std: bool,
+    },
+    /// Report the functions calling `todo!`, `unimplemented!` or `panic!`,
+    /// with how often they call each
+    #[clap(alias = "count-panics")]
+    AuditPanics,
+    /// Report how many fields of struct NAME, or of every struct, are
+    /// public, listing them, to audit encapsulation
+    #[clap(alias = "count-public-fields")]
+    StructVis { name: Option<String> },
+    /// Print a content hash of each top-level item, for storing and later
+    /// comparing with `--changed-since`
+    Hashes {
+        /// Report the items added, removed or changed since the hashes in
+        /// HASHFILE were printed
+        #[clap(long, value_name = "HASHFILE")]
+        changed_since: Option<PathBuf>,
+    },
+    /// Check that items like `fn:parse` or `struct:Config` exist, failing
+    /// with the missing ones otherwise
+    Require {
+        #[clap(required = true)]
+        names: Vec<String>,
+    },
+    /// Count the public items reachable from the crate root, by kind
+    CountPubApi {
+        /// Print the counts as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Extract functions. Names are looked up as free functions, with
+    /// paths like `parser::lex` into inline modules, then names like
+    /// `Type::name` as methods of `Type`, then, with `--fallback-methods`,
+    /// bare names as methods of any type.
+    #[clap(alias = "f")]
+    Function {
+        #[clap(required = true)]
+        names: Vec<String>,
+        /// Look up names that aren't free functions as methods of any type
+        #[clap(long)]
+        fallback_methods: bool,
+    },
+    #[clap(alias = "s")]
+    Struct(Names),
+    #[clap(alias = "e")]
+    Enum(Names),
+    #[clap(alias = "t")]
+    Trait(Names),
+    /// Print a trait's supertraits and the declarations of its associated
+    /// items, without default bodies
+    TraitInfo { name: String },
+    /// Extract functions with a signature matching a pattern like
+    /// `fn(_, &str) -> Result<_, _>`, where `_` matches any type
+    MatchSig { pattern: String },
+    /// Extract `trait Foo = Bar + Baz;` aliases
+    #[clap(alias = "ta")]
+    TraitAlias(Names),
+    #[clap(alias = "c")]
+    Const(Names),
+    #[clap(alias = "ec")]
+    ExternCrate(Names),
+    #[clap(alias = "st")]
+    Static {
+        #[clap(flatten)]
+        names: Names,
+        /// Only extract `static mut` items
+        #[clap(long = "mut")]
+        mut_only: bool,
+    },
+    #[clap(alias = "ty")]
+    Type(Names),
+    #[clap(alias = "u")]
+    Union(Names),
+    /// Note: output might be mangled
+    #[clap(alias = "m")]
+    Macro(Names),
+    /// Extract invocations of a function-like macro at module scope, like
+    /// `lazy_static! { ... }`, as written in the source. PATH can be the
+    /// macro's full path or its last segment.
+    MacroCall { path: String },
+    /// Extract `extern` blocks, optionally only the ones with the given ABI
+    ExternBlock { abi: Option<String> },
+    /// Extract functions and statics declared in `extern` blocks, wrapped in
+    /// their block
+    Foreign(Names),
+    /// Diff an item against the item of the same name in OTHER. NAME can
+    /// have a kind prefix, like `fn:parse`.
+    Diff {
+        other: PathBuf,
+        name: String,
+        /// Read OTHER as it was at this git revision
+        #[clap(long, value_name = "REV")]
+        other_rev: Option<String>,
+    },
+    /// Print the parameters of a function or `Type::method` as `name: Type`,
+    /// one per line
+    Params { name: String },
+    /// Print the return type of a function or `Type::method`, `()` if it
+    /// has none
+    ReturnType { name: String },
+    /// Print the variants of a C-like enum with their discriminant values
+    Discriminants { name: String },
+    /// Print a Graphviz DOT graph of the references between top-level
+    /// items, for `dot -Tpng`
+    Graph {
+        /// Which references to draw edges for
+        #[clap(long, value_enum, default_value = "both")]
+        kind: deps::Edges,
+    },
+    /// List the top-level items referring to NAME
+    UsersOf {
+        name: String,
+        /// Extract the items instead of listing them
+        #[clap(long)]
+        full: bool,
+    },
+    /// Extract the top-level `use` declarations
+    Uses,
+    /// Print all items of the file with the items of inline modules hoisted
+    /// to the top level
+    FlattenMods,
+    /// Print the attributes of an item, one per line. NAME can have a kind
+    /// prefix, like `struct:Config`.
+    Attrs { name: String },
+    /// Print the fields of a struct with their values in the file's `impl
+    /// Default`, or `Default::default()` if it's derived
+    Defaultable { name: String },
+    /// Print the value of a `const` or `static`
+    ConstValue {
+        name: String,
+        /// Print only the body of the closure the value is
+        #[clap(long)]
+        closure_body: bool,
+    },
+    /// Print the declared type of a `const`, `static` or `Struct::field`
+    TypeOf { name: String },
+    /// Extract all `#[test]` functions, including ones in nested modules
+    Tests {
+        /// Extract the `#[cfg(test)]` modules containing tests as a whole
+        #[clap(long)]
+        with_module: bool,
+    },
+    /// Print names of items of KIND starting with PREFIX, for shell
+    /// completion
+    #[clap(name = "complete-names", alias = "__complete", hide = true)]
+    Complete {
+        kind: Kind,
+        #[clap(default_value = "")]
+        prefix: String,
+    },
+    /// Extract all impl blocks for a type
+    Impls {
+        ty: String,
+        /// Only impls of this trait
+        #[clap(long = "trait")]
+        trait_: Option<String>,
+    },
+    /// Extract a method of TYPE, wrapped in an `impl TYPE` block
+    Method {
+        ty: String,
+        name: String,
+        /// Keep the generics, where clause and trait of the enclosing impl,
+        /// so the output compiles in isolation
+        #[clap(long)]
+        context_impl: bool,
+    },
+    /// Extract every method NAME in the impls of any type, like all the
+    /// `new` constructors, grouped by type
+    AnyMethod {
+        name: String,
+        /// Keep the generics, where clause and trait of the enclosing impls
+        #[clap(long)]
+        context_impl: bool,
+    },
+    /// Extract impl blocks with a bound like `T: Clone` in their generics or
+    /// where clause
+    ImplsWhere { bound: String },
+    /// Extract structs, enums and unions with a `#[repr]` including REPR,
+    /// like `C`, `transparent`, `u8` or `packed`
+    Repr { repr: String },
+    /// Extract `impl Drop for TYPE`
+    DropImpl { ty: String },
+    /// Extract `impl Deref`/`impl DerefMut` for TYPE
+    DerefImpl { ty: String },
+    /// Extract operator trait impls (`Add`, `Neg`, `Index`, ...) for TYPE
+    OpImpl { ty: String },
+}
+
+const DEREF_TRAITS: &[&str] = &["Deref", "DerefMut"];
+const OPERATOR_TRAITS: &[&str] = &[
+    "Add",
+    "AddAssign",
+    "BitAnd",
+    "BitAndAssign",
+    "BitOr",
+    "BitOrAssign",
+    "BitXor",
+    "BitXorAssign",
+    "Div",
+    "DivAssign",
+    "Index",
+    "IndexMut",
+    "Mul",
+    "MulAssign",
+    "Neg",
+    "Not",
+    "Rem",
+    "RemAssign",
+    "Shl",
+    "ShlAssign",
+    "Shr",
+    "ShrAssign",
+    "Sub",
+    "SubAssign",
+];
+
+#[derive(Args)]
+struct Names {
+    #[clap(required = true)]
+    names: Vec<String>,
+}
+
+#[derive(Args)]
+struct ExtractOpts {
+    /// Replace uses of type aliases defined in the file with their definitions
+    #[clap(long, global = true)]
+    inline_aliases: bool,
+    /// Extract every item with a matching name, not just the first one
+    #[clap(long, global = true)]
+    all: bool,
+    /// Start the output with a table of contents of the extracted items
+    #[clap(long, global = true)]
+    toc: bool,
+    /// Drop all attributes of the extracted items except these kinds, e.g.
+    /// `--keep-attrs doc,cfg`
+    #[clap(
+        long,
+        global = true,
+        value_name = "ATTRS",
+        use_value_delimiter = true
+    )]
+    keep_attrs: Option<Vec<String>>,
+    /// Put `#[allow(dead_code, unused)]`, or an `#[allow]` of the given
+    /// lints, like `--allow=clippy::all,unused`, on the extracted items
+    #[clap(
+        long,
+        global = true,
+        value_name = "LINTS",
+        min_values = 0,
+        require_equals = true,
+        use_value_delimiter = true,
+        value_parser = parse_lint
+    )]
+    allow: Option<Vec<String>>,
+    /// Remove these derives from the `#[derive]`s of the extracted items,
+    /// e.g. `--strip-derive Debug`, dropping derives left empty
+    #[clap(
+        long,
+        global = true,
+        value_name = "DERIVE",
+        use_value_delimiter = true,
+        multiple_occurrences = true
+    )]
+    strip_derive: Vec<String>,
+    /// Print the parsed syntax tree of the extracted items instead of code,
+    /// for debugging
+    #[clap(long, global = true)]
+    debug_ast: bool,
+    /// Break up lists, and bounds in where clauses, in lines longer than N
+    /// characters
+    #[clap(long, global = true, value_name = "N")]
+    max_width: Option<usize>,
+    /// Indent with N spaces instead of 4
+    #[clap(long, global = true, value_name = "N", conflicts_with = "tabs")]
+    indent: Option<usize>,
+    /// Indent with tabs instead of 4 spaces
+    #[clap(long, global = true)]
+    tabs: bool,
+    /// Print the original source of the items, keeping comments and
+    /// formatting. Only `--keep-attrs` applies, the other transforms don't.
+    #[clap(long, global = true)]
+    keep_comments: bool,
+    /// With `--keep-comments`, print items that are next to each other in
+    /// the source as one slice of it, keeping the comments between them.
+    /// Items are printed in source order.
+    #[clap(
+        long,
+        global = true,
+        requires = "keep-comments",
+        conflicts_with_all = &["toc", "split-dir"]
+    )]
+    span_gap: bool,
+    /// Sort the names in `use` groups, and `use` declarations among
+    /// themselves
+    #[clap(long, global = true)]
+    sort_use: bool,
+    /// Rename the extracted item, which has to be the only one
+    #[clap(long, global = true, value_name = "NAME")]
+    rename: Option<String>,
+    /// With `--rename`, also rename references to the item inside it
+    #[clap(long, global = true, requires = "rename")]
+    rename_refs: bool,
+    /// Rewrite the extracted items, where `rename=OLD:NEW` renames every
+    /// occurrence of the identifier OLD, definitions and references. Can be
+    /// given several times.
+    #[clap(
+        long,
+        global = true,
+        value_name = "TRANSFORM",
+        multiple_occurrences = true,
+        value_parser = parse_transform
+    )]
+    transform: Vec<(String, String)>,
+    /// Only keep associated functions without `self` in extracted impls
+    #[clap(long, global = true, conflicts_with = "methods-only")]
+    associated_only: bool,
+    /// Only keep methods taking `self` in extracted impls
+    #[clap(long, global = true)]
+    methods_only: bool,
+    /// Remove `#[cfg]` attributes from the extracted items, instead of
+    /// keeping them and noting the ones of enclosing modules in a comment
+    #[clap(long, global = true, conflicts_with = "keep-comments")]
+    drop_cfg: bool,
+    /// Precede items having const generic parameters with a comment listing
+    /// them
+    #[clap(long, global = true)]
+    show_const_generics: bool,
+    /// Write each extracted item to `DIR/<name>.rs`, with the name in snake
+    /// case, instead of printing it, with a `DIR/mod.rs` declaring them and
+    /// importing them by name
+    #[clap(long, global = true, value_name = "DIR")]
+    split_dir: Option<PathBuf>,
+    /// Also extract the parts of the top-level `use` declarations that
+    /// import names the extracted items refer to
+    #[clap(long, global = true)]
+    context_uses: bool,
+    /// Print the extracted items in the order they appear in the source,
+    /// rather than in the order they were asked for
+    #[clap(long, global = true)]
+    source_order: bool,
+    /// Also extract the top-level items the extracted ones refer to,
+    /// recursively
+    #[clap(long, global = true)]
+    with_deps: bool,
+    /// Rename generic parameters to `T0`, `T1`, ... (and `'a0`, `N0` for
+    /// lifetimes and consts), for comparing items
+    #[clap(long, global = true)]
+    normalize_generics: bool,
+    /// Move inline bounds of generic parameters, like `<T: Clone>`, into
+    /// where clauses
+    #[clap(long, global = true)]
+    canonical_bounds: bool,
+    /// Change the visibility of the extracted items, like `pub` or
+    /// `pub(crate)`. For inherent impls, the members are changed instead.
+    #[clap(
+        long,
+        global = true,
+        value_name = "VIS",
+        value_parser = parse_vis
+    )]
+    vis_bump: Option<String>,
+    /// With `--vis-bump`, change the visibility of struct fields too
+    #[clap(long, global = true, requires = "vis-bump")]
+    vis_bump_fields: bool,
+    /// Wrap the output into a rust-script, with a cargo manifest template
+    /// and a `fn main()` stub when extracting functions
+    #[clap(long, global = true)]
+    rust_script: bool,
+    /// Wrap the output in a ```` ```rust ```` fence, for pasting into
+    /// documentation
+    #[clap(long, global = true, conflicts_with = "rust-script")]
+    doctest: bool,
+    /// Remove doc comments from the extracted items and their fields,
+    /// variants and members
+    #[clap(long, global = true)]
+    no_docs: bool,
+    /// Print each item on a single line with minimal whitespace. Literals are
+    /// left untouched, comments are dropped.
+    #[clap(long, global = true)]
+    compact: bool,
+    /// Only list or extract items with an attribute whose path ends with
+    /// NAME, like `deprecated` or `inline`
+    #[clap(long, global = true, value_name = "NAME")]
+    has_attr: Option<String>,
+    /// Only list or extract `const fn`s, and only keep those in extracted
+    /// impls
+    #[clap(long, global = true)]
+    const_fn: bool,
+    /// Only list or extract `async fn`s, and only keep those in extracted
+    /// impls
+    #[clap(long = "async", global = true)]
+    async_fn: bool,
+    /// Rewrite `crate::`, `self::` and `super::` paths to start with ROOT.
+    /// Without ROOT, strip them and the module names after them, and drop
+    /// such `use` declarations, so the items refer to each other.
+    #[clap(
+        long,
+        global = true,
+        value_name = "ROOT",
+        require_equals = true,
+        value_parser = parse_root
+    )]
+    rewrite_paths: Option<Option<String>>,
+    /// Text put between extracted items, where `\n` and `\t` are a newline
+    /// and a tab, like `'\n// ---\n\n'`
+    #[clap(
+        long,
+        global = true,
+        value_name = "STRING",
+        default_value = "\\n",
+        value_parser = parse_separator
+    )]
+    separator: String,
+    /// Prefix each line with the line number it had in the source. Only
+    /// exact with `--keep-comments`, as unparsing moves code around.
+    #[clap(long, global = true)]
+    line_numbers: bool,
+    /// Check that the output of each item parses as Rust again, failing if
+    /// it doesn't
+    #[clap(
+        long,
+        global = true,
+        conflicts_with_all = &["debug-ast", "line-numbers"]
+    )]
+    verify: bool,
+}
+
+fn parse_vis(vis: &str) -> Result<String, String> {
+    match syn::parse_str::<Visibility>(vis) {
+        Ok(Visibility::Inherited) | Err(_) => {
+            Err(format!("`{}` isn't a visibility like `pub`", vis))
+        }
+        Ok(_) => Ok(vis.to_string()),
+    }
+}
+
+fn parse_separator(sep: &str) -> Result<String, String> {
+    Ok(sep.replace("\\n", "\n").replace("\\t", "\t"))
+}
+
+fn parse_lint(lint: &str) -> Result<String, String> {
+    match syn::parse_str::<syn::Path>(lint) {
+        Ok(_) => Ok(lint.to_string()),
+        Err(_) => Err(format!("`{}` isn't a lint name", lint)),
+    }
+}
+
+/// Parses `rename=OLD:NEW` into the identifiers
+fn parse_transform(transform: &str) -> Result<(String, String), String> {
+    let invalid =
+        || format!("`{}` isn't a transform like `rename=Old:New`", transform);
+    let pair = transform.strip_prefix("rename=").ok_or_else(invalid)?;
+    let (old, new) = pair.split_once(':').ok_or_else(invalid)?;
+    for ident in [old, new] {
+        if syn::parse_str::<Ident>(ident).is_err() {
+            return Err(format!("`{}` isn't an identifier", ident));
+        }
+    }
+    Ok((old.to_string(), new.to_string()))
+}
+
+fn parse_position(pos: &str) -> Result<LineColumn, String> {
+    let invalid = || format!("`{}` isn't a position like `42:10`", pos);
+    let (line, column) = pos.split_once(':').ok_or_else(invalid)?;
+    match (line.parse::<usize>(), column.parse::<usize>()) {
+        (Ok(line @ 1..), Ok(column @ 1..)) => Ok(LineColumn {
+            line,
+            column: column - 1,
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_root(root: &str) -> Result<String, String> {
+    match syn::parse_str::<Ident>(root) {
+        Ok(_) => Ok(root.to_string()),
+        Err(_) => Err(format!("`{}` isn't a crate name", root)),
+    }
+}
+
+#[derive(Parser)]
+#[clap(subcommand_precedence_over_arg = true, after_help = AFTER_HELP)]
+struct Opt {
+    /// Files to read; directories are searched for `.rs` files. With
+    /// `--workspace`, `Cargo.toml` files or directories containing one.
+    /// Members of archives can be read like `foo-0.1.0.crate:src/lib.rs`,
+    /// with the `archive` feature.
+    paths: Vec<PathBuf>,
+    /// Print a completion script for the given shell
+    #[clap(long, value_name = "SHELL", value_parser)]
+    completions: Option<Shell>,
+    #[clap(subcommand)]
+    item: Option<ExtractItem>,
+    /// Extract the items named in a file, one per line, optionally prefixed
+    /// with a kind (`fn parse` or `fn:parse`)
+    #[clap(long, value_name = "FILE")]
+    names_from: Option<PathBuf>,
+    /// Extract the innermost item at this position, like `42:10`, as
+    /// reported by editors, with 1-based line and column
+    #[clap(
+        long,
+        value_name = "LINE:COL",
+        value_parser = parse_position,
+        conflicts_with = "names-from"
+    )]
+    at: Option<LineColumn>,
+    /// Report how long reading, parsing, searching and unparsing took to
+    /// stderr
+    #[clap(long)]
+    time: bool,
+    /// Only parse the file, reporting timing and item count to stderr
+    #[clap(long, hide = true)]
+    parse_only: bool,
+    /// Read the files as they were at this git revision
+    #[clap(long, global = true, value_name = "REV")]
+    since_git: Option<String>,
+    /// Read the files as Markdown, extracting from their Rust code blocks
+    #[clap(long, global = true)]
+    from_markdown: bool,
+    /// Wrap the output of each input file in a module named after it
+    #[clap(long)]
+    merge_files: bool,
+    /// Search the `src` directories of all members of the workspace
+    #[clap(long)]
+    workspace: bool,
+    #[clap(flatten)]
+    extract: ExtractOpts,
+    /// Only consider items inside inline modules matching this path, where
+    /// `*` matches anything (e.g. `internal::*`)
+    #[clap(long, global = true)]
+    module: Option<String>,
+    /// Set a cfg option, like `unix` or `feature="std"`
+    #[clap(long = "cfg", global = true)]
+    cfgs: Vec<String>,
+    /// Remove code whose `#[cfg]` isn't satisfied by the `--cfg` options
+    #[clap(long, global = true)]
+    strip_cfg_gated: bool,
+    /// Remove `#[cfg(test)]` modules and `#[test]` functions before
+    /// listing or extracting, for a view of the production code
+    #[clap(long, global = true)]
+    strip_test_mod: bool,
+    /// Number of files to process in parallel, defaults to the number of CPUs
+    #[clap(long, short, global = true, value_name = "N")]
+    jobs: Option<usize>,
+    /// Plain ASCII output without colors or other decorations, for CI logs
+    /// and older terminals
+    #[clap(long, global = true)]
+    ascii: bool,
+    /// Refuse to print more than N lines to a terminal
+    #[clap(long, global = true, value_name = "N", default_value_t = 10_000)]
+    max_lines: usize,
+    /// Print output longer than `--max-lines` anyway
+    #[clap(long, global = true, alias = "yes")]
+    force: bool,
+}
+
+const AFTER_HELP: &str = "OUTPUT ORDER:
+    Output is always deterministic: items come in source order or the order
+    they were asked for, and files in the order given, even with --jobs, so
+    the same query prints the same bytes every time.
+
+EXIT STATUS:
+    0  success
+    1  an item wasn't found
+    2  an input file couldn't be parsed
+    3  a file couldn't be read or written
+    4  invalid arguments
+    5  a check, like `list --undocumented --strict`, found problems";
+
+/// What to do with each input file
+enum Task<'a> {
+    ParseOnly,
+    Command(&'a ExtractItem),
+    /// Names read from `--names-from`
+    Names(Vec<String>),
+    /// Position given with `--at`
+    At(LineColumn),
+}
+
+fn main() {
+    let args = expand_aliases(env::args_os().collect());
+    let opt = match Opt::try_parse_from(args) {
+        Ok(opt) => opt,
+        Err(e) if e.use_stderr() => {
+            let _ = e.print();
+            process::exit(error::EXIT_USAGE)
+        }
+        // --help and --version
+        Err(e) => e.exit(),
+    };
+    if opt.ascii {
+        owo_colors::set_override(false);
+    }
+    if let Some(shell) = opt.completions {
+        return print_completions(shell);
+    }
+    if let Some(ExtractItem::Kinds) = opt.item {
+        if let Err(e) = print_kinds(&mut io::stdout().lock()) {
+            fail(e)
+        }
+        return;
+    }
+    if opt.paths.is_empty() {
+        usage_error(
+            ErrorKind::MissingRequiredArgument,
+            "at least one input path is required",
+        )
+    }
+    let task = match (&opt.item, &opt.names_from) {
+        _ if opt.parse_only => Task::ParseOnly,
+        (Some(_), _) if opt.at.is_some() => usage_error(
+            ErrorKind::ArgumentConflict,
+            "--at can't be combined with a subcommand",
+        ),
+        (Some(item), None) => Task::Command(item),
+        (None, Some(path)) => match fs::read_to_string(path) {
+            Ok(list) => Task::Names(list.lines().map(Into::into).collect()),
+            Err(e) => fail(Error::Read(path.clone(), e)),
+        },
+        (Some(_), Some(_)) => usage_error(
+            ErrorKind::ArgumentConflict,
+            "--names-from can't be combined with a subcommand",
+        ),
+        (None, None) => match opt.at {
+            Some(pos) => Task::At(pos),
+            None => usage_error(
+                ErrorKind::MissingSubcommand,
+                "a subcommand, --names-from or --at is required",
+            ),
+        },
+    };
+    let result = run(&opt, &task);
+    if opt.time {
+        timing::report();
+    }
+    if let Err(e) = result {
+        fail(e)
+    }
+}
+
+/// Prints the output, unless it's going to a terminal and is longer than
+/// `--max-lines`, in which case nothing is printed
+fn run(opt: &Opt, task: &Task) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    if opt.force || !stdout.is_terminal() {
+        return run_to(opt, task, &mut stdout);
+    }
+    let mut out = LineLimit {
+        held: vec![],
+        lines: 0,
+        max: opt.max_lines,
+    };
+    let result = run_to(opt, task, &mut out);
+    if out.lines > opt.max_lines {
+        return Err(Error::Usage(format!(
+            "output has {} lines, more than --max-lines {}; pass --force to \
+             print it anyway, or redirect it to a file",
+            out.lines, opt.max_lines
+        )));
+    }
+    stdout.write_all(&out.held)?;
+    result
+}
+
+/// Holds on to output of up to `max` lines, and only counts the lines once
+/// there are more, so a huge output isn't kept around just to be refused
+struct LineLimit {
+    held: Vec<u8>,
+    lines: usize,
+    max: usize,
+}
+
+impl Write for LineLimit {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lines += buf.iter().filter(|&&b| b == b'\n').count();
+        match self.lines <= self.max {
+            true => self.held.extend_from_slice(buf),
+            false => self.held = vec![],
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run_to(opt: &Opt, task: &Task, stdout: &mut dyn Write) -> Result<()> {
+    let (files, crates) = match opt.workspace {
+        true => input::collect_workspace(&opt.paths)?.into_iter().unzip(),
+        false => (input::collect(&opt.paths)?, vec![]),
+    };
+    if let ([path], false) = (&files[..], opt.merge_files) {
+        return timing::FILES.time(|| process_file(opt, task, path, stdout));
+    }
+    let results = input::map_files(opt.jobs, &files, |path| {
+        let mut out = vec![];
+        let result =
+            timing::FILES.time(|| process_file(opt, task, path, &mut out));
+        (out, result)
+    });
+    // only names missing from every file are reported as not found
+    let mut missing: Option<Vec<String>> = None;
+    let mut failed = 0;
+    // exit code of the first failure
+    let mut code = None;
+    let mut modules = HashSet::new();
+    for (i, (path, (out, result))) in files.iter().zip(results).enumerate() {
+        if !out.is_empty() && opt.merge_files {
+            let name = module_name(path, &mut modules);
+            writeln!(stdout, "// {}", path.display())?;
+            writeln!(stdout, "mod {} {{", name)?;
+            let text = String::from_utf8_lossy(&out);
+            write!(stdout, "{}", format::indent(&text, "    "))?;
+            writeln!(stdout, "}}\n")?;
+        } else if !out.is_empty() {
+            match crates.get(i) {
+                Some(name) => {
+                    writeln!(stdout, "// {}: {}", name, path.display())?
+                }
+                None => writeln!(stdout, "// {}", path.display())?,
+            }
+            stdout.write_all(&out)?;
+            writeln!(stdout)?;
+        }
+        let file_missing = match result {
+            Ok(()) => vec![],
+            Err(Error::NotFound(what)) => what,
+            Err(e) => {
+                code.get_or_insert(e.exit_code());
+                report(e);
+                failed += 1;
+                continue;
+            }
+        };
+        missing = Some(match missing {
+            Some(m) => {
+                m.into_iter().filter(|w| file_missing.contains(w)).collect()
+            }
+            None => file_missing,
+        });
+    }
+    match missing {
+        Some(missing) if !missing.is_empty() => Err(Error::NotFound(missing)),
+        _ if failed > 0 => Err(Error::Files(failed, code.unwrap_or(1))),
+        _ => Ok(()),
+    }
+}
+
+/// Turns the stem of `path` into a module name that isn't in `used` yet,
+/// appending a number to names that are, with a warning
+fn module_name(path: &Path, used: &mut HashSet<String>) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = mod_ident(file_stem(&stem));
+    if used.insert(name.clone()) {
+        return name;
+    }
+    let unique = (2..)
+        .map(|n| format!("{}_{}", name, n))
+        .find(|n| used.insert(n.clone()))
+        .unwrap();
+    warn(&format!(
+        "module `{}` already exists, using `{}` for {}",
+        name,
+        unique,
+        path.display()
+    ));
+    unique
+}
+
+/// Makes a file stem a valid module name, by prefixing ones not starting
+/// with a letter with `_` and suffixing keywords with `_`
+fn mod_ident(mut name: String) -> String {
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    // keywords, including ones syn still allows as identifiers
+    let reserved = ["async", "await", "dyn", "try"].contains(&name.as_str());
+    if reserved || syn::parse_str::<Ident>(&name).is_err() {
+        name.push('_');
+    }
+    name
+}
+
+/// Reads and parses a file, from git revision `rev` if given, applying the
+/// cfg and module filters
+fn load(opt: &Opt, path: &Path, rev: Option<&str>) -> Result<Source> {
+    let content = match rev {
+        Some(rev) => timing::READ.time(|| input::git_show(path, rev))?,
+        None => match input::split_archive(path) {
+            Some((archive, member)) => {
+                timing::READ.time(|| input::read_archive(archive, member))?
+            }
+            None => timing::READ
+                .time(|| fs::read_to_string(path))
+                .map_err(|e| Error::Read(path.to_owned(), e))?,
+        },
+    };
+    let (content, blocks) = match opt.from_markdown {
+        true => markdown::rust_blocks(&content),
+        false => (content, vec![]),
+    };
+    let mut file = timing::PARSE
+        .time(|| syn::parse_file(&content))
+        .map_err(|e| Error::parse(path.to_owned(), e))?;
+    if opt.strip_cfg_gated {
+        cfg::CfgSet::new(&opt.cfgs).strip(&mut file);
+    }
+    if opt.strip_test_mod {
+        transform::strip_tests(&mut file);
+    }
+    if let Some(pattern) = &opt.module {
+        file = modules::scope(file, pattern).ok_or_else(|| {
+            Error::not_found(format!("module matching `{}`", pattern))
+        })?;
+    }
+    let mut src = Source::new(content, file);
+    src.blocks = blocks;
+    Ok(src)
+}
+
+fn process_file(
+    opt: &Opt,
+    task: &Task,
+    path: &Path,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let start = Instant::now();
+    let src = load(opt, path, opt.since_git.as_deref())?;
+    let file = &src.file;
+    let item = match task {
+        Task::ParseOnly => {
+            eprintln!(
+                "{}: parsed {} items in {:.2?}",
+                path.display(),
+                file.items.len(),
+                start.elapsed()
+            );
+            return Ok(());
+        }
+        Task::Command(item) => item,
+        Task::Names(names) => {
+            return extract_names(&src, &opt.extract, names, out)
+        }
+        Task::At(pos) => {
+            let item =
+                modules::item_at(&file.items, *pos).ok_or_else(|| {
+                    let what =
+                        format!("item at {}:{}", pos.line, pos.column + 1);
+                    Error::not_found(what)
+                })?;
+            return print_items(&src, &opt.extract, vec![item], out);
+        }
+    };
+    match item {
+        ExtractItem::ListItems(list_opts) => {
+            let has_attr = opt.extract.has_attr.as_deref();
+            let (const_fn, async_fn) =
+                (opt.extract.const_fn, opt.extract.async_fn);
+            // a line at a time would be a write per line to a line
+            // buffered stdout, slow for files with thousands of items
+            let mut out = BufWriter::new(out);
+            list::list(
+                file, list_opts, has_attr, const_fn, async_fn, &mut out,
+            )?;
+            out.flush()?;
+            Ok(())
+        }
+        ExtractItem::Kinds => print_kinds(out),
+        ExtractItem::LintDups => lint_dups(file, out),
+        ExtractItem::AuditUnsafe { no_std } => {
+            audit::unsafe_report(file, out)?;
+            match no_std {
+                true => no_std::report(file, out),
+                false => Ok(()),
+            }
+        }
+        ExtractItem::AuditPanics => audit::panic_report(file, out),
+        ExtractItem::StructVis { name } => {
+            audit::field_vis(file, name.as_deref(), out)
+        }
+        ExtractItem::Hashes { changed_since } => match changed_since {
+            Some(path) => hash::changed_since(file, path, out),
+            None => hash::print(file, out),
+        },
+        ExtractItem::Require { names } => require(file, names, out),
+        ExtractItem::CountPubApi { json } => api::print(file, *json, out),
+        ExtractItem::Function {
+            names,
+            fallback_methods,
+        } => {
+            let opts = &opt.extract;
+            extract_with(&src, names, opts, out, |name| {
+                let found = find::<ItemFn>(file, name, opts.all);
+                if !found.is_empty() {
+                    return found;
+                }
+                let mut methods = match name.rsplit_once("::") {
+                    Some((ty, method)) => {
+                        find_methods(file, Some(ty), method, false, opts)
+                    }
+                    None if *fallback_methods => {
+                        find_methods(file, None, name, false, opts)
+                    }
+                    None => vec![],
+                };
+                if !opts.all {
+                    methods.truncate(1);
+                }
+                methods
+            })
+        }
+        ExtractItem::Struct(n) => {
+            extract::<ItemStruct>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Enum(n) => {
+            extract::<ItemEnum>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Trait(n) => {
+            extract::<ItemTrait>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::TraitInfo { name } => {
+            let mut t = match Kind::Trait.find(file, name, false).pop() {
+                Some(Item::Trait(t)) => t,
+                _ => return Err(Error::not_found(format!("trait `{}`", name))),
+            };
+            t.attrs.clear();
+            transform::trait_contract(&mut t);
+            print_items(&src, &opt.extract, vec![Item::Trait(t)], out)
+        }
+        ExtractItem::MatchSig { pattern } => {
+            let pattern = signature::parse_pattern(pattern)?;
+            let fns: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| match item {
+                    Item::Fn(f) => signature::matches(&pattern, &f.sig),
+                    _ => false,
+                })
+                .cloned()
+                .collect();
+            if fns.is_empty() {
+                return Err(Error::not_found("functions matching the pattern"));
+            }
+            print_items(&src, &opt.extract, fns, out)
+        }
+        ExtractItem::TraitAlias(n) => {
+            extract::<ItemTraitAlias>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Const(n) => {
+            extract::<ItemConst>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::ExternCrate(n) => {
+            extract::<ItemExternCrate>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Static { names, mut_only } => {
+            extract_with(&src, &names.names, &opt.extract, out, |name| {
+                let mut found = find::<ItemStatic>(file, name, opt.extract.all);
+                if *mut_only {
+                    found.retain(|item| {
+                        matches!(item, Item::Static(s) if s.mutability.is_some())
+                    });
+                }
+                found
+            })
+        }
+        ExtractItem::Type(n) => {
+            extract::<ItemType>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Union(n) => {
+            extract::<ItemUnion>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::Macro(n) => {
+            extract::<ItemMacro>(&src, &n.names, &opt.extract, out)
+        }
+        ExtractItem::ExternBlock { abi } => {
+            let blocks: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| match item {
+                    Item::ForeignMod(m) => {
+                        abi.is_none() || abi.as_deref() == Some(&abi_name(m))
+                    }
+                    _ => false,
+                })
+                .cloned()
+                .collect();
+            if blocks.is_empty() {
+                let what = match abi {
+                    Some(abi) => format!("extern \"{}\" blocks", abi),
+                    None => "extern blocks".to_string(),
+                };
+                return Err(Error::not_found(what));
+            }
+            print_items(&src, &opt.extract, blocks, out)
+        }
+        ExtractItem::Foreign(n) => {
+            extract_with(&src, &n.names, &opt.extract, out, |name| {
+                find_foreign(file, name, opt.extract.all)
+            })
+        }
+        ExtractItem::Diff {
+            other,
+            name,
+            other_rev,
+        } => {
+            let other_src = load(opt, other, other_rev.as_deref())?;
+            let find = |file| match parse_kind_name(name) {
+                (Some(kind), name) => kind.find(file, name, false),
+                (None, name) => find_any(file, name, false),
+            };
+            let label = |path: &Path, rev: Option<&str>, item: &Item| {
+                let at = rev.map(|rev| format!("@{}", rev)).unwrap_or_default();
+                format!("{}{}: {}", path.display(), at, item_label(item))
+            };
+            let rev = opt.since_git.as_deref();
+            match (find(file).pop(), find(&other_src.file).pop()) {
+                (Some(old), Some(new)) => diff::print(
+                    (&label(path, rev, &old), &finish(&src, &opt.extract, old)),
+                    (
+                        &label(other, other_rev.as_deref(), &new),
+                        &finish(&other_src, &opt.extract, new),
+                    ),
+                    out,
+                ),
+                _ => Err(Error::not_found(format!("`{}` in both files", name))),
+            }
+        }
+        ExtractItem::Params { name } => {
+            let sig = match fn_signature(file, name, &opt.extract) {
+                Some(sig) => sig,
+                None => return Err(Error::not_found(format!("fn `{}`", name))),
+            };
+            for arg in &sig.inputs {
+                writeln!(out, "{}", unparse_arg(arg))?;
+            }
+            Ok(())
+        }
+        ExtractItem::ReturnType { name } => {
+            let sig = match fn_signature(file, name, &opt.extract) {
+                Some(sig) => sig,
+                None => return Err(Error::not_found(format!("fn `{}`", name))),
+            };
+            match sig.output {
+                ReturnType::Default => writeln!(out, "()")?,
+                ReturnType::Type(_, ty) => {
+                    writeln!(out, "{}", unparse_type(&ty))?
+                }
+            }
+            Ok(())
+        }
+        ExtractItem::Discriminants { name } => discriminants(file, name, out),
+        ExtractItem::FlattenMods => {
+            let items = transform::flatten_mods(file.items.clone()).map_err(
+                |dups| {
+                    Error::Usage(format!(
+                        "flattening would define {} more than once",
+                        dups.join(", ")
+                    ))
+                },
+            )?;
+            print_items(&src, &opt.extract, items, out)
+        }
+        ExtractItem::Graph { kind } => deps::graph(file, *kind, out),
+        ExtractItem::UsersOf { name, full } => {
+            let users = deps::users_of(file, name);
+            if users.is_empty() {
+                return Err(Error::not_found(format!("users of `{}`", name)));
+            }
+            if *full {
+                let users = users.into_iter().cloned().collect();
+                return print_items(&src, &opt.extract, users, out);
+            }
+            for item in users {
+                writeln!(out, "{}", item_label(item))?;
+            }
+            Ok(())
+        }
+        ExtractItem::Uses => {
+            let mut uses: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| matches!(item, Item::Use(_)))
+                .cloned()
+                .collect();
+            if uses.is_empty() {
+                return Err(Error::not_found("use declarations"));
+            }
+            if opt.extract.sort_use {
+                transform::sort_uses(&mut uses);
+            }
+            print_items(&src, &opt.extract, uses, out)
+        }
+        ExtractItem::ConstValue { name, closure_body } => {
+            const_value(file, name, *closure_body, out)
+        }
+        ExtractItem::Attrs { name } => {
+            let found = match parse_kind_name(name) {
+                (Some(kind), n) => kind.find(file, n, false),
+                (None, n) => find_any(file, n, false),
+            };
+            let item = match found.into_iter().next() {
+                Some(item) => item,
+                None => return Err(Error::not_found(format!("`{}`", name))),
+            };
+            for attr in item_attrs(&item) {
+                writeln!(out, "{}", unparse_attr(attr))?;
+            }
+            Ok(())
+        }
+        ExtractItem::MacroCall { path } => {
+            let mut calls = macro_calls(file).filter(|(p, _)| {
+                p == path || p.rsplit("::").next() == Some(path.as_str())
+            });
+            let calls: Vec<_> = match opt.extract.all {
+                true => calls.map(|(_, m)| m).collect(),
+                false => calls.next().map(|(_, m)| m).into_iter().collect(),
+            };
+            if calls.is_empty() {
+                let what = format!("invocation of `{}!`", path);
+                return Err(Error::not_found(what));
+            }
+            // macro bodies would get mangled by unparsing
+            let keep = opt.extract.keep_attrs.as_deref();
+            let rendered: Vec<_> = calls
+                .into_iter()
+                .map(|m| verbatim(&src, &Item::Macro(m.clone()), keep))
+                .collect();
+            write!(out, "{}", rendered.join(&opt.extract.separator))?;
+            Ok(())
+        }
+        ExtractItem::Defaultable { name } => defaultable(file, name, out),
+        ExtractItem::TypeOf { name } => type_of(file, name, out),
+        ExtractItem::Tests { with_module } => {
+            let mut tests = vec![];
+            collect_tests(&file.items, *with_module, &mut tests);
+            if tests.is_empty() {
+                return Err(Error::not_found("test functions"));
+            }
+            print_items(&src, &opt.extract, tests, out)
+        }
+        ExtractItem::Complete { kind, prefix } => {
+            let mut names: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| Kind::of(item) == Some(*kind))
+                .filter_map(|item| Some(item_info(item)?.1.to_string()))
+                .filter(|name| name.starts_with(prefix.as_str()))
+                .collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                writeln!(out, "{}", name)?;
+            }
+            Ok(())
+        }
+        ExtractItem::Impls { ty, trait_ } => {
+            let trait_ = trait_.as_deref();
+            let traits = trait_.as_ref().map(std::slice::from_ref);
+            print_impls(&src, &opt.extract, ty, traits, out)
+        }
+        ExtractItem::Method {
+            ty,
+            name,
+            context_impl,
+        } => {
+            let opts = &opt.extract;
+            let methods =
+                find_methods(file, Some(ty), name, *context_impl, opts);
+            if methods.is_empty() {
+                let what = format!("method `{}::{}`", ty, name);
+                return Err(Error::not_found(what));
+            }
+            print_items(&src, &opt.extract, methods, out)
+        }
+        ExtractItem::AnyMethod { name, context_impl } => {
+            let opts = &opt.extract;
+            let methods = find_methods(file, None, name, *context_impl, opts);
+            if methods.is_empty() {
+                let what = format!("method `{}` in any impl", name);
+                return Err(Error::not_found(what));
+            }
+            print_items(&src, &opt.extract, methods, out)
+        }
+        ExtractItem::ImplsWhere { bound } => {
+            let wanted = parse_bounds(bound)?;
+            let impls: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| match item {
+                    Item::Impl(i) => {
+                        let have = generics_bounds(&i.generics);
+                        wanted.iter().all(|b| have.contains(b))
+                    }
+                    _ => false,
+                })
+                .cloned()
+                .collect();
+            if impls.is_empty() {
+                let what = format!("impls with bound `{}`", bound);
+                return Err(Error::not_found(what));
+            }
+            print_items(&src, &opt.extract, impls, out)
+        }
+        ExtractItem::Repr { repr } => {
+            let types: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| {
+                    matches!(
+                        item,
+                        Item::Struct(_) | Item::Enum(_) | Item::Union(_)
+                    ) && reprs(item_attrs(item)).iter().any(|r| r == repr)
+                })
+                .cloned()
+                .collect();
+            if types.is_empty() {
+                let what = format!("types with #[repr({})]", repr);
+                return Err(Error::not_found(what));
+            }
+            print_items(&src, &opt.extract, types, out)
+        }
+        ExtractItem::DropImpl { ty } => {
+            print_impls(&src, &opt.extract, ty, Some(&["Drop"]), out)
+        }
+        ExtractItem::DerefImpl { ty } => {
+            print_impls(&src, &opt.extract, ty, Some(DEREF_TRAITS), out)
+        }
+        ExtractItem::OpImpl { ty } => {
+            print_impls(&src, &opt.extract, ty, Some(OPERATOR_TRAITS), out)
+        }
+    }
+}
+
+/// Prints the value of a const or static, or with `closure_body` the body of
+/// the closure it is, looking through parentheses and casts like `(|| 1) as
+/// fn() -> u8`
+fn const_value(
+    file: &File,
+    name: &str,
+    closure_body: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let found = Kind::Const.find(file, name, false).into_iter();
+    let expr = match found.chain(Kind::Static.find(file, name, false)).next() {
+        Some(Item::Const(c)) => c.expr,
+        Some(Item::Static(s)) => s.expr,
+        _ => {
+            let what = format!("const or static `{}`", name);
+            return Err(Error::not_found(what));
+        }
+    };
+    let expr = match closure_body {
+        true => body_of_closure(&expr).ok_or_else(|| {
+            Error::Usage(format!("`{}` is not a closure", name))
+        })?,
+        false => &expr,
+    };
+    writeln!(out, "{}", unparse_expr(expr))?;
+    Ok(())
+}
+
+/// Looks for a const or static first, so that module paths like
+/// `consts::MAX` work, then for a field of a struct or union, by name or by
+/// index for tuple structs
+fn type_of(file: &File, name: &str, out: &mut dyn Write) -> Result<()> {
+    let found = Kind::Const.find(file, name, false).into_iter();
+    let ty = match found.chain(Kind::Static.find(file, name, false)).next() {
+        Some(Item::Const(c)) => *c.ty,
+        Some(Item::Static(s)) => *s.ty,
+        _ => match field_type(file, name) {
+            Some(ty) => ty,
+            None => {
+                if let Some(item) = find_any(file, name, false).pop() {
+                    let kind = item_info(&item).map_or("item", |(k, _)| k);
+                    return Err(Error::Usage(format!(
+                        "`{}` is a {}, which has no declared type",
+                        name, kind
+                    )));
+                }
+                let what = format!("const, static or field `{}`", name);
+                return Err(Error::not_found(what));
+            }
+        },
+    };
+    writeln!(out, "{}", unparse_type(&ty))?;
+    Ok(())
+}
+
+fn field_type(file: &File, name: &str) -> Option<syn::Type> {
+    let (ty, field) = name.rsplit_once("::")?;
+    let fields = match find_any(file, ty, false).pop()? {
+        Item::Struct(s) => s.fields,
+        Item::Union(u) => Fields::Named(u.fields),
+        _ => return None,
+    };
+    let found = fields
+        .into_iter()
+        .enumerate()
+        .find(|(i, f)| match &f.ident {
+            Some(ident) => ident == field,
+            None => i.to_string() == field,
+        });
+    found.map(|(_, f)| f.ty)
+}
+
+fn body_of_closure(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Closure(c) => Some(&c.body),
+        Expr::Paren(p) => body_of_closure(&p.expr),
+        Expr::Group(g) => body_of_closure(&g.expr),
+        Expr::Cast(c) => body_of_closure(&c.expr),
+        _ => None,
+    }
+}
+
+/// Module-level macro invocations, which aren't `macro_rules!` definitions,
+/// with their paths like `std::thread_local`
+fn macro_calls(file: &File) -> impl Iterator<Item = (String, &ItemMacro)> {
+    file.items.iter().filter_map(|item| match item {
+        Item::Macro(m) if m.ident.is_none() => {
+            Some((path_text(&m.mac.path), m))
+        }
+        _ => None,
+    })
+}
+
+/// Prints `field: Type = value` lines, taking the values from the struct
+/// expression in `Default::default()`. Fields without a value there, like
+/// ones covered by `..`, are printed without one.
+fn defaultable(file: &File, name: &str, out: &mut dyn Write) -> Result<()> {
+    let s = match Kind::Struct.find(file, name, false).pop() {
+        Some(Item::Struct(s)) => s,
+        _ => return Err(Error::not_found(format!("struct `{}`", name))),
+    };
+    let derived = s.attrs.iter().any(|attr| {
+        attr.path.is_ident("derive")
+            && match attr.parse_meta() {
+                Ok(Meta::List(list)) => list.nested.iter().any(|nested| {
+                    matches!(nested, NestedMeta::Meta(Meta::Path(p))
+                        if p.segments.last().is_some_and(|s| s.ident == "Default"))
+                }),
+                _ => false,
+            }
+    });
+    let default_fn = find_impls(file, name, Some(&["Default"]))
+        .into_iter()
+        .flat_map(|i| &i.items)
+        .find_map(|member| match member {
+            ImplItem::Method(m) if m.sig.ident == "default" => Some(&m.block),
+            _ => None,
+        });
+    let mut values = DefaultValues::new(name);
+    if let Some(block) = default_fn {
+        values.visit_block(block);
+    }
+    if !derived && default_fn.is_none() {
+        warn(&format!("`{}` has no Default impl in the file", name));
+    }
+    for (i, field) in s.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => i.to_string(),
+        };
+        let ty = unparse_type(&field.ty);
+        let value = match values.found.get(&member) {
+            Some(expr) => Some(unparse_expr(expr)),
+            None if derived => Some("Default::default()".to_string()),
+            None => None,
+        };
+        match value {
+            Some(value) => writeln!(out, "{}: {} = {}", member, ty, value)?,
+            None => writeln!(out, "{}: {}", member, ty)?,
+        }
+    }
+    Ok(())
+}
+
+/// Finds the field values of the first `Self { .. }`, `Name { .. }`,
+/// `Self(..)` or `Name(..)` expression
+struct DefaultValues<'a> {
+    name: &'a str,
+    found: HashMap<String, Expr>,
+    done: bool,
+}
+
+impl<'a> DefaultValues<'a> {
+    fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            found: HashMap::new(),
+            done: false,
+        }
+    }
+
+    fn is_self(&self, path: &syn::Path) -> bool {
+        path.segments
+            .last()
+            .is_some_and(|s| s.ident == "Self" || s.ident == self.name)
+    }
+}
+
+impl<'ast> Visit<'ast> for DefaultValues<'_> {
+    fn visit_expr_struct(&mut self, e: &'ast ExprStruct) {
+        if self.done || !self.is_self(&e.path) {
+            return visit::visit_expr_struct(self, e);
+        }
+        self.done = true;
+        for field in &e.fields {
+            let member = match &field.member {
+                Member::Named(ident) => ident.to_string(),
+                Member::Unnamed(index) => index.index.to_string(),
+            };
+            self.found.insert(member, field.expr.clone());
+        }
+    }
+
+    fn visit_expr_call(&mut self, e: &'ast ExprCall) {
+        let ctor = matches!(&*e.func, Expr::Path(p) if self.is_self(&p.path));
+        if self.done || !ctor {
+            return visit::visit_expr_call(self, e);
+        }
+        self.done = true;
+        for (i, arg) in e.args.iter().enumerate() {
+            self.found.insert(i.to_string(), arg.clone());
+        }
+    }
+}
+
+/// Prints `Variant = value` lines, counting up from the last explicit value
+/// for variants without one. Values that aren't integer literals are printed
+/// as written, with `+ n` for the variants after them.
+fn discriminants(file: &File, name: &str, out: &mut dyn Write) -> Result<()> {
+    let e = match Kind::Enum.find(file, name, false).pop() {
+        Some(Item::Enum(e)) => e,
+        _ => return Err(Error::not_found(format!("enum `{}`", name))),
+    };
+    // the last explicit value as an integer or an expression, and how many
+    // variants came after it
+    let mut base: (Result<i128, String>, i128) = (Ok(0), 0);
+    for variant in &e.variants {
+        if let Some((_, expr)) = &variant.discriminant {
+            base = (int_literal(expr).ok_or_else(|| unparse_expr(expr)), 0);
+        }
+        let value = match &base {
+            (Ok(n), offset) => (n + offset).to_string(),
+            (Err(expr), 0) => expr.clone(),
+            (Err(expr), offset) => format!("{} + {}", expr, offset),
+        };
+        writeln!(out, "{} = {}", variant.ident, value)?;
+        base.1 += 1;
+    }
+    Ok(())
+}
+
+/// Value of an integer literal expression like `3`, `-1` or `0x10u8`
+fn int_literal(expr: &Expr) -> Option<i128> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(i), ..
+        }) => i.base10_parse().ok(),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => int_literal(expr).map(|n: i128| -n),
+        Expr::Paren(p) => int_literal(&p.expr),
+        _ => None,
+    }
+}
+
+fn collect_tests(items: &[Item], with_module: bool, tests: &mut Vec<Item>) {
+    for item in items {
+        match item {
+            Item::Fn(f) if f.attrs.iter().any(|a| attr_is(a, "test")) => {
+                tests.push(item.clone())
+            }
+            Item::Mod(m) => {
+                let Some((_, content)) = &m.content else {
+                    continue;
+                };
+                let mut inner = vec![];
+                collect_tests(content, with_module, &mut inner);
+                if with_module && is_cfg_test(&m.attrs) && !inner.is_empty() {
+                    tests.push(item.clone());
+                } else {
+                    tests.extend(inner);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn lint_dups(file: &File, out: &mut dyn Write) -> Result<()> {
+    // ordered so that output doesn't depend on hashing, even for ties
+    let mut seen: BTreeMap<String, Vec<(&str, usize)>> = BTreeMap::new();
+    for item in &file.items {
+        if let Some((kind, name)) = item_info(item) {
+            let line = item.span().start().line;
+            seen.entry(name.to_string()).or_default().push((kind, line));
+        }
+    }
+    let mut dups: Vec<_> = seen
+        .into_iter()
+        .filter(|(_, locs)| locs.len() > 1)
+        .collect();
+    dups.sort_by_key(|(_, locs)| locs[0].1);
+    if dups.is_empty() {
+        writeln!(out, "No duplicate items found")?;
+    }
+    for (name, locs) in dups {
+        writeln!(
+            out,
+            "Duplicate {}:",
+            name.if_supports_color(Stdout, |n| n.purple())
+        )?;
+        for (kind, line) in locs {
+            let kind =
+                kind.if_supports_color(Stdout, |k| k.style(kind_style()));
+            writeln!(out, "{:>12} at line {}", kind, line)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_completions(shell: Shell) {
+    let mut cmd = Opt::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, &name, &mut io::stdout());
+    if shell == Shell::Bash {
+        // complete item names by asking the file itself
+        print!("{}", BASH_NAME_COMPLETION);
+    }
+}
+
+const BASH_NAME_COMPLETION: &str = r#"
+_code_extractor_names() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if (( COMP_CWORD >= 3 )) && [[ -f "${COMP_WORDS[1]}" ]]; then
+        local names
+        if names=$(code-extractor "${COMP_WORDS[1]}" __complete "$prev" "$cur" 2>/dev/null); then
+            COMPREPLY=($names)
+            return
+        fi
+    fi
+    _code-extractor "$@"
+}
+complete -F _code_extractor_names -o bashdefault -o default code-extractor
+"#;
+
+fn report(error: Error) {
+    eprintln!(
+        "{} {}",
+        "error:"
+            .if_supports_color(Stderr, |e| e.style(Style::new().red().bold())),
+        error
+    );
+}
+
+fn warn(message: &str) {
+    let style = Style::new().yellow().bold();
+    let label = "warning:".if_supports_color(Stderr, |w| w.style(style));
+    eprintln!("{} {}", label, message);
+}
+
+fn fail(error: Error) -> ! {
+    let code = error.exit_code();
+    report(error);
+    process::exit(code)
+}
+
+/// Reports a usage error the way clap does, with the usage exit code
+fn usage_error(kind: ErrorKind, message: &str) -> ! {
+    let _ = Opt::command().error(kind, message).print();
+    process::exit(error::EXIT_USAGE)
+}
+
+fn print_impls(
+    src: &Source,
+    opts: &ExtractOpts,
+    ty: &str,
+    traits: Option<&[&str]>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let impls = find_impls(&src.file, ty, traits);
+    if impls.is_empty() {
+        return Err(Error::not_found(match traits {
+            Some(traits) => {
+                format!("impl of {} for `{}`", traits.join("/"), ty)
+            }
+            None => format!("impls for `{}`", ty),
+        }));
+    }
+    let impls = impls
+        .into_iter()
+        .filter_map(|i| {
+            let mut i = i.clone();
+            if opts.associated_only
+                || opts.methods_only
+                || opts.const_fn
+                || opts.async_fn
+            {
+                i.items.retain(|item| keep_member(opts, item));
+                if i.items.is_empty() {
+                    return None;
+                }
+            }
+            Some(i.into_item())
+        })
+        .collect();
+    print_items(src, opts, impls, out)
+}
+
+/// Finds impl blocks whose self type is named `ty`. With `traits`, only
+/// trait impls whose trait path ends in one of the given names are kept.
+fn find_impls<'a>(
+    file: &'a File,
+    ty: &str,
+    traits: Option<&[&str]>,
+) -> Vec<&'a ItemImpl> {
+    file.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Impl(i) => Some(i),
+            _ => None,
+        })
+        .filter(|i| type_name(&i.self_ty).is_some_and(|n| n == ty))
+        .filter(|i| match (traits, &i.trait_) {
+            (None, _) => true,
+            (Some(traits), Some((_, path, _))) => path
+                .segments
+                .last()
+                .is_some_and(|s| traits.iter().any(|t| s.ident == t)),
+            (Some(_), None) => false,
+        })
+        .collect()
+}
+
+/// Finds methods `name` in the impls of `ty`, each in a copy of its impl
+/// holding only that method. Without `context`, the impl is reduced to a bare
+/// `impl Type`. Without `ty`, all methods `name` of any type are found,
+/// grouped by type.
+fn find_methods(
+    file: &File,
+    ty: Option<&str>,
+    name: &str,
+    context: bool,
+    opts: &ExtractOpts,
+) -> Vec<Item> {
+    let impls = match ty {
+        Some(ty) => find_impls(file, ty, None),
+        None => {
+            let mut impls: Vec<_> = file
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Impl(i) => Some(i),
+                    _ => None,
+                })
+                .collect();
+            impls.sort_by_key(|i| type_name(&i.self_ty));
+            impls
+        }
+    };
+    impls
+        .into_iter()
+        .flat_map(|i| {
+            i.items
+                .iter()
+                .filter(|item| {
+                    matches!(item, ImplItem::Method(m) if m.sig.ident == name)
+                        && keep_member(opts, item)
+                })
+                .map(move |method| {
+                    let mut i = i.clone();
+                    i.items = vec![method.clone()];
+                    if !context {
+                        i.attrs.clear();
+                        i.generics = Generics::default();
+                        i.trait_ = None;
+                        i.unsafety = None;
+                        i.defaultness = None;
+                    }
+                    Item::Impl(i)
+                })
+        })
+        .take(if opts.all || ty.is_none() {
+            usize::MAX
+        } else {
+            1
+        })
+        .collect()
+}
+
+/// Applies `--associated-only`, `--methods-only`, `--const-fn` and `--async`
+/// to impl members, which all leave only functions
+fn keep_member(opts: &ExtractOpts, item: &ImplItem) -> bool {
+    if let (true, ImplItem::Method(m)) = (opts.const_fn, item) {
+        if m.sig.constness.is_none() {
+            return false;
+        }
+    }
+    if let (true, ImplItem::Method(m)) = (opts.async_fn, item) {
+        if m.sig.asyncness.is_none() {
+            return false;
+        }
+    }
+    if !opts.associated_only && !opts.methods_only {
+        let fns_only = opts.const_fn || opts.async_fn;
+        return !fns_only || matches!(item, ImplItem::Method(_));
+    }
+    match item {
+        ImplItem::Method(m) => {
+            // syn parses `self: Box<Self>` as a typed argument
+            let method = match m.sig.inputs.first() {
+                Some(FnArg::Receiver(_)) => true,
+                Some(FnArg::Typed(arg)) => {
+                    matches!(&*arg.pat, Pat::Ident(p) if p.ident == "self")
+                }
+                None => false,
+            };
+            method == opts.methods_only
+        }
+        _ => false,
+    }
+}
+
+/// Parses a where clause predicate like `T: Clone + Send` into single
+/// bounds, as from [`predicate_bounds`]
+fn parse_bounds(bound: &str) -> Result<Vec<(String, String)>> {
+    let predicate = syn::parse_str(bound).map_err(|e| {
+        Error::Usage(format!("invalid bound `{}`: {}", bound, e))
+    })?;
+    Ok(predicate_bounds(&predicate))
+}
+
+/// Splits a predicate into `(bounded, bound)` pairs of whitespace-free token
+/// strings, so `T: Clone + Send` becomes `("T", "Clone"), ("T", "Send")`
+fn predicate_bounds(predicate: &WherePredicate) -> Vec<(String, String)> {
+    let tokens = |t: &dyn ToTokens| {
+        let s = t.to_token_stream().to_string();
+        s.split_whitespace().collect::<String>()
+    };
+    match predicate {
+        WherePredicate::Type(p) => {
+            let ty = tokens(&p.bounded_ty);
+            p.bounds.iter().map(|b| (ty.clone(), tokens(b))).collect()
+        }
+        WherePredicate::Lifetime(p) => {
+            let lt = tokens(&p.lifetime);
+            p.bounds.iter().map(|b| (lt.clone(), tokens(b))).collect()
+        }
+        WherePredicate::Eq(p) => vec![(tokens(&p.lhs_ty), tokens(&p.rhs_ty))],
+    }
+}
+
+/// All bounds of the generic parameters, inline and in the where clause
+fn generics_bounds(generics: &Generics) -> Vec<(String, String)> {
+    let inline = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(p) => {
+            let (ident, bounds) = (&p.ident, &p.bounds);
+            Some(parse_quote!(#ident: #bounds))
+        }
+        GenericParam::Lifetime(p) if !p.bounds.is_empty() => {
+            let (lt, bounds) = (&p.lifetime, &p.bounds);
+            Some(parse_quote!(#lt: #bounds))
+        }
+        _ => None,
+    });
+    let clause = generics.where_clause.iter().flat_map(|w| &w.predicates);
+    inline
+        .collect::<Vec<WherePredicate>>()
+        .iter()
+        .chain(clause)
+        .flat_map(predicate_bounds)
+        .collect()
+}
+
+/// Name of the last path segment of a type, ignoring generics
+fn type_name(ty: &syn::Type) -> Option<&Ident> {
+    match ty {
+        syn::Type::Path(p) => Some(&p.path.segments.last()?.ident),
+        _ => None,
+    }
+}
+
+fn item_info(item: &Item) -> Option<(&'static str, &Ident)> {
+    match item {
+        Item::Fn(i) => Some(("fn", &i.sig.ident)),
+        Item::Struct(i) => Some(("struct", &i.ident)),
+        Item::Enum(i) => Some(("enum", &i.ident)),
+        Item::Trait(i) => Some(("trait", &i.ident)),
+        Item::TraitAlias(i) => Some(("trait alias", &i.ident)),
+        Item::Const(i) => Some(("const", &i.ident)),
+        Item::ExternCrate(i) => Some(("extern crate", &i.ident)),
+        Item::Static(i) if i.mutability.is_some() => {
+            Some(("static mut", &i.ident))
+        }
+        Item::Static(i) => Some(("static", &i.ident)),
+        Item::Type(i) => Some(("type", &i.ident)),
+        Item::Union(i) => Some(("union", &i.ident)),
+        Item::Macro(ItemMacro { ident: Some(i), .. }) => Some(("macro", i)),
+        _ => None,
+    }
+}
+
+/// Kind and name of an item declared in an `extern` block
+fn foreign_info(item: &ForeignItem) -> Option<(&'static str, &Ident)> {
+    match item {
+        ForeignItem::Fn(i) => Some(("extern fn", &i.sig.ident)),
+        ForeignItem::Static(i) if i.mutability.is_some() => {
+            Some(("extern static mut", &i.ident))
+        }
+        ForeignItem::Static(i) => Some(("extern static", &i.ident)),
+        ForeignItem::Type(i) => Some(("extern type", &i.ident)),
+        _ => None,
+    }
+}
+
+/// ABI of an `extern` block, where a bare `extern` means `"C"`
+fn abi_name(block: &ItemForeignMod) -> String {
+    block
+        .abi
+        .name
+        .as_ref()
+        .map_or("C".to_string(), |abi| abi.value())
+}
+
+/// Finds foreign items named `name`, each returned inside a copy of its
+/// `extern` block that holds only that item
+fn find_foreign(file: &File, name: &str, all: bool) -> Vec<Item> {
+    let blocks = file.items.iter().filter_map(|item| match item {
+        Item::ForeignMod(m) => Some(m),
+        _ => None,
+    });
+    blocks
+        .flat_map(|block| {
+            block
+                .items
+                .iter()
+                .filter(|i| foreign_info(i).is_some_and(|(_, i)| i == name))
+                .map(move |i| {
+                    let mut block = block.clone();
+                    block.items = vec![i.clone()];
+                    Item::ForeignMod(block)
+                })
+        })
+        .take(if all { usize::MAX } else { 1 })
+        .collect()
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(i) => &i.attrs,
+        Item::Enum(i) => &i.attrs,
+        Item::ExternCrate(i) => &i.attrs,
+        Item::Fn(i) => &i.attrs,
+        Item::ForeignMod(i) => &i.attrs,
+        Item::Impl(i) => &i.attrs,
+        Item::Macro(i) => &i.attrs,
+        Item::Macro2(i) => &i.attrs,
+        Item::Mod(i) => &i.attrs,
+        Item::Static(i) => &i.attrs,
+        Item::Struct(i) => &i.attrs,
+        Item::Trait(i) => &i.attrs,
+        Item::TraitAlias(i) => &i.attrs,
+        Item::Type(i) => &i.attrs,
+        Item::Union(i) => &i.attrs,
+        Item::Use(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+/// Label used to refer to an item in messages, like `fn foo`
+fn item_label(item: &Item) -> String {
+    match (item_info(item), item) {
+        (Some((kind, name)), _) => format!("{} {}", kind, name),
+        (None, Item::Impl(i)) => format!("impl {}", impl_name(i)),
+        (None, Item::Macro(m)) => format!("{}!", path_text(&m.mac.path)),
+        (None, Item::ForeignMod(m)) => match m.items.as_slice() {
+            [i] => match foreign_info(i) {
+                Some((kind, name)) => format!("{} {}", kind, name),
+                None => "extern item".to_string(),
+            },
+            _ => format!("extern \"{}\" block", abi_name(m)),
+        },
+        _ => "item".to_string(),
+    }
+}
+
+/// Joins the segments of a path like `std::thread_local`, without generics
+fn path_text(path: &syn::Path) -> String {
+    let segments = path.segments.iter();
+    let segments: Vec<_> = segments.map(|s| s.ident.to_string()).collect();
+    segments.join("::")
+}
+
+/// Describes an impl block like `Display for Foo`
+fn impl_name(i: &ItemImpl) -> String {
+    let ty = type_name(&i.self_ty)
+        .map_or_else(|| "_".to_string(), |i| i.to_string());
+    match &i.trait_ {
+        Some((_, path, _)) => {
+            let tr = &path.segments.last().unwrap().ident;
+            format!("{} for {}", tr, ty)
+        }
+        None => ty,
+    }
+}
+
+/// Item kinds that can be named on the command line, as in `fn:parse`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Fn,
+    Struct,
+    Enum,
+    Trait,
+    TraitAlias,
+    Const,
+    ExternCrate,
+    Static,
+    Type,
+    Union,
+    Macro,
+}
+
+/// Each kind's name, subcommand and short aliases. The aliases have to match
+/// the ones on `ExtractItem`.
+const KINDS: &[(Kind, &str, &str, &[&str])] = &[
+    (Kind::Fn, "fn", "function", &["f"]),
+    (Kind::Struct, "struct", "struct", &["s"]),
+    (Kind::Enum, "enum", "enum", &["e"]),
+    (Kind::Trait, "trait", "trait", &["t"]),
+    (Kind::TraitAlias, "trait-alias", "trait-alias", &["ta"]),
+    (Kind::Const, "const", "const", &["c"]),
+    (Kind::ExternCrate, "extern-crate", "extern-crate", &["ec"]),
+    (Kind::Static, "static", "static", &["st"]),
+    (Kind::Type, "type", "type", &["ty"]),
+    (Kind::Union, "union", "union", &["u"]),
+    (Kind::Macro, "macro", "macro", &["m"]),
+];
+
+/// Environment variable with extra aliases, like `fun=fn,tr=trait`
+const ALIASES_VAR: &str = "CODE_EXTRACTOR_ALIASES";
+
+/// Aliases from `CODE_EXTRACTOR_ALIASES`, skipping ones naming unknown kinds
+fn custom_aliases() -> Vec<(String, Kind)> {
+    let aliases = env::var(ALIASES_VAR).unwrap_or_default();
+    aliases
+        .split(',')
+        .filter_map(|alias| {
+            let (alias, kind) = alias.split_once('=')?;
+            Some((alias.trim().to_string(), builtin_kind(kind.trim())?))
+        })
+        .collect()
+}
+
+fn builtin_kind(s: &str) -> Option<Kind> {
+    let s = s.replace('_', "-");
+    KINDS
+        .iter()
+        .find(|(_, name, cmd, aliases)| {
+            *name == s || *cmd == s || aliases.contains(&&*s)
+        })
+        .map(|(kind, ..)| *kind)
+}
+
+/// Subcommand of a kind, for expanding custom aliases on the command line
+fn subcommand(kind: Kind) -> &'static str {
+    KINDS.iter().find(|(k, ..)| *k == kind).unwrap().2
 }
 
-#[derive(Parser)]
-struct Opt {
-    filename: PathBuf,
-    #[clap(subcommand)]
-    item: ExtractItem,
+/// Replaces the first argument that is a custom alias, and not an existing
+/// file, with the subcommand it stands for
+fn expand_aliases(mut args: Vec<OsString>) -> Vec<OsString> {
+    let aliases = custom_aliases();
+    let found = args.iter().enumerate().skip(1).find_map(|(i, arg)| {
+        let arg = arg.to_str()?;
+        let (_, kind) = aliases.iter().find(|(a, _)| a == arg)?;
+        (!Path::new(arg).exists()).then_some((i, *kind))
+    });
+    if let Some((i, kind)) = found {
+        args[i] = subcommand(kind).into();
+    }
+    args
 }
 
-fn main() {
-    let opt = Opt::parse();
-    let file_content = fs::read_to_string(opt.filename).unwrap();
-    let file = syn::parse_file(&file_content).unwrap();
-    match opt.item {
-        ExtractItem::ListItems => {
-            println!("Listing items:");
-            for item in file.items {
-                let info = match item {
-                    Item::Fn(i) => Some(("fn", i.sig.ident)),
-                    Item::Struct(i) => Some(("struct", i.ident)),
-                    Item::Enum(i) => Some(("enum", i.ident)),
-                    Item::Trait(i) => Some(("trait", i.ident)),
-                    Item::Const(i) => Some(("const", i.ident)),
-                    Item::ExternCrate(i) => Some(("extern crate", i.ident)),
-                    Item::Static(i) => Some(("static", i.ident)),
-                    Item::Type(i) => Some(("type", i.ident)),
-                    Item::Union(i) => Some(("union", i.ident)),
-                    Item::Macro(ItemMacro { ident: Some(i), .. }) => {
-                        Some(("macro", i))
-                    }
-                    _ => None,
-                };
-                if let Some((kind, name)) = info {
-                    println!("{:>12} {}", kind.green().bold(), name.purple());
+fn print_kinds(out: &mut dyn Write) -> Result<()> {
+    let custom = custom_aliases();
+    writeln!(out, "{:<14}{:<14}aliases", "kind", "subcommand")?;
+    for (kind, name, cmd, aliases) in KINDS {
+        let mut aliases: Vec<_> =
+            aliases.iter().map(|a| a.to_string()).collect();
+        aliases.extend(
+            custom
+                .iter()
+                .filter(|(_, k)| k == kind)
+                .map(|(a, _)| a.clone()),
+        );
+        writeln!(out, "{:<14}{:<14}{}", name, cmd, aliases.join(", "))?;
+    }
+    writeln!(
+        out,
+        "\nMore aliases can be set in {}, like `fun=fn,tr=trait`",
+        ALIASES_VAR
+    )?;
+    Ok(())
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let custom = || custom_aliases().into_iter().find(|(a, _)| a == s);
+        match builtin_kind(s) {
+            Some(kind) => Ok(kind),
+            None => match custom() {
+                Some((_, kind)) => Ok(kind),
+                None => Err(format!("unknown item kind `{}`", s)),
+            },
+        }
+    }
+}
+
+impl Kind {
+    fn of(item: &Item) -> Option<Self> {
+        Some(match item {
+            Item::Fn(_) => Kind::Fn,
+            Item::Struct(_) => Kind::Struct,
+            Item::Enum(_) => Kind::Enum,
+            Item::Trait(_) => Kind::Trait,
+            Item::TraitAlias(_) => Kind::TraitAlias,
+            Item::Const(_) => Kind::Const,
+            Item::ExternCrate(_) => Kind::ExternCrate,
+            Item::Static(_) => Kind::Static,
+            Item::Type(_) => Kind::Type,
+            Item::Union(_) => Kind::Union,
+            Item::Macro(_) => Kind::Macro,
+            _ => return None,
+        })
+    }
+
+    fn find(self, file: &File, name: &str, all: bool) -> Vec<Item> {
+        match self {
+            Kind::Fn => find::<ItemFn>(file, name, all),
+            Kind::Struct => find::<ItemStruct>(file, name, all),
+            Kind::Enum => find::<ItemEnum>(file, name, all),
+            Kind::Trait => find::<ItemTrait>(file, name, all),
+            Kind::TraitAlias => find::<ItemTraitAlias>(file, name, all),
+            Kind::Const => find::<ItemConst>(file, name, all),
+            Kind::ExternCrate => find::<ItemExternCrate>(file, name, all),
+            Kind::Static => find::<ItemStatic>(file, name, all),
+            Kind::Type => find::<ItemType>(file, name, all),
+            Kind::Union => find::<ItemUnion>(file, name, all),
+            Kind::Macro => find::<ItemMacro>(file, name, all),
+        }
+    }
+}
+
+/// Splits `fn parse` or `fn:parse` into a kind and a name. Anything without
+/// a recognized kind prefix is taken as a bare name.
+fn parse_kind_name(s: &str) -> (Option<Kind>, &str) {
+    let split = s
+        .split_once(char::is_whitespace)
+        .or_else(|| s.split_once(':').filter(|(_, n)| !n.starts_with(':')));
+    match split {
+        Some((kind, name)) => match kind.parse() {
+            Ok(kind) => (Some(kind), name.trim()),
+            Err(_) => (None, s),
+        },
+        None => (None, s),
+    }
+}
+
+fn find<T: Find + Unparse + Clone>(
+    file: &File,
+    name: &str,
+    all: bool,
+) -> Vec<Item> {
+    let found: Vec<_> = if all {
+        T::find_all(file, name)
+    } else {
+        T::find(file, name).into_iter().collect()
+    };
+    found.into_iter().map(|i| i.clone().into_item()).collect()
+}
+
+/// Finds items of any kind named `name`
+fn find_any(file: &File, name: &str, all: bool) -> Vec<Item> {
+    let (items, name) = modules::resolve(&file.items, name);
+    items
+        .into_iter()
+        .filter(|item| item_info(item).is_some_and(|(_, i)| i == name))
+        .take(if all { usize::MAX } else { 1 })
+        .cloned()
+        .collect()
+}
+
+fn item_attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    match item {
+        Item::Const(i) => Some(&mut i.attrs),
+        Item::Enum(i) => Some(&mut i.attrs),
+        Item::ExternCrate(i) => Some(&mut i.attrs),
+        Item::Fn(i) => Some(&mut i.attrs),
+        Item::ForeignMod(i) => Some(&mut i.attrs),
+        Item::Impl(i) => Some(&mut i.attrs),
+        Item::Macro(i) => Some(&mut i.attrs),
+        Item::Macro2(i) => Some(&mut i.attrs),
+        Item::Mod(i) => Some(&mut i.attrs),
+        Item::Static(i) => Some(&mut i.attrs),
+        Item::Struct(i) => Some(&mut i.attrs),
+        Item::Trait(i) => Some(&mut i.attrs),
+        Item::TraitAlias(i) => Some(&mut i.attrs),
+        Item::Type(i) => Some(&mut i.attrs),
+        Item::Union(i) => Some(&mut i.attrs),
+        Item::Use(i) => Some(&mut i.attrs),
+        _ => None,
+    }
+}
+
+/// Names of the representations in `#[repr]` attributes, like `C` and
+/// `packed` for `#[repr(C, packed(2))]`
+fn reprs(attrs: &[Attribute]) -> Vec<String> {
+    let lists =
+        attrs
+            .iter()
+            .filter(|a| a.path.is_ident("repr"))
+            .filter_map(|attr| match attr.parse_meta() {
+                Ok(Meta::List(list)) => Some(list.nested),
+                _ => None,
+            });
+    let metas = lists.flatten().filter_map(|nested| match nested {
+        NestedMeta::Meta(meta) => meta.path().get_ident().map(Ident::to_string),
+        NestedMeta::Lit(_) => None,
+    });
+    metas.collect()
+}
+
+/// Whether the attribute's path ends with `name`, so `test` matches both
+/// `#[test]` and `#[tokio::test]`
+fn attr_is(attr: &Attribute, name: &str) -> bool {
+    attr.path.segments.last().is_some_and(|s| s.ident == name)
+}
+
+fn is_cfg_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path.is_ident("cfg") && attr.tokens.to_string() == "(test)"
+    })
+}
+
+fn extract<T: Find + Unparse + Clone>(
+    src: &Source,
+    names: &[String],
+    opts: &ExtractOpts,
+    out: &mut dyn Write,
+) -> Result<()> {
+    extract_with(src, names, opts, out, |name| {
+        find::<T>(&src.file, name, opts.all)
+    })
+}
+
+/// Prints the items `find` returns for each of `names`, reporting the names
+/// it found nothing for
+fn extract_with(
+    src: &Source,
+    names: &[String],
+    opts: &ExtractOpts,
+    out: &mut dyn Write,
+    find: impl Fn(&str) -> Vec<Item>,
+) -> Result<()> {
+    let mut items = vec![];
+    let mut missing = vec![];
+    for name in names {
+        let found = find(name);
+        if found.is_empty() {
+            missing.push(format!("`{}`", name));
+        }
+        items.extend(found);
+    }
+    print_items(src, opts, items, out)?;
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(Error::NotFound(missing)),
+    }
+}
+
+/// Extracts names given as lines of `--names-from`, skipping blank lines and
+/// `#` comments
+fn extract_names(
+    src: &Source,
+    opts: &ExtractOpts,
+    lines: &[String],
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut items = vec![];
+    let mut missing = vec![];
+    for line in lines.iter().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let found = match parse_kind_name(line) {
+            (Some(kind), name) => kind.find(&src.file, name, opts.all),
+            (None, name) => find_any(&src.file, name, opts.all),
+        };
+        if found.is_empty() {
+            missing.push(format!("`{}`", line));
+        }
+        items.extend(found);
+    }
+    print_items(src, opts, items, out)?;
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(Error::NotFound(missing)),
+    }
+}
+
+fn require(file: &File, names: &[String], out: &mut dyn Write) -> Result<()> {
+    let mut missing = vec![];
+    for name in names {
+        let found = match parse_kind_name(name) {
+            (Some(kind), n) => !kind.find(file, n, false).is_empty(),
+            (None, n) => !find_any(file, n, false).is_empty(),
+        };
+        if !found {
+            missing.push(format!("`{}`", name));
+        }
+        let status = format!("{:>7}", if found { "ok" } else { "missing" });
+        let style = match found {
+            true => Style::new().green(),
+            false => Style::new().red(),
+        };
+        let status = status.if_supports_color(Stdout, |s| s.style(style));
+        writeln!(out, "{} {}", status, name)?;
+    }
+    let found = names.len() - missing.len();
+    writeln!(out, "{} of {} required items found", found, names.len())?;
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(Error::NotFound(missing)),
+    }
+}
+
+fn print_items(
+    src: &Source,
+    opts: &ExtractOpts,
+    mut items: Vec<Item>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if let Some(name) = &opts.has_attr {
+        items.retain(|i| item_attrs(i).iter().any(|attr| attr_is(attr, name)));
+        if items.is_empty() {
+            return Err(Error::not_found(format!("items with #[{}]", name)));
+        }
+    }
+    if opts.const_fn {
+        items.retain(|item| match item {
+            Item::Fn(f) => f.sig.constness.is_some(),
+            Item::Impl(i) => !i.items.is_empty(),
+            _ => false,
+        });
+        if items.is_empty() {
+            return Err(Error::not_found("const fns"));
+        }
+    }
+    if opts.async_fn {
+        items.retain(|item| match item {
+            Item::Fn(f) => f.sig.asyncness.is_some(),
+            Item::Impl(i) => !i.items.is_empty(),
+            _ => false,
+        });
+        if items.is_empty() {
+            return Err(Error::not_found("async fns"));
+        }
+    }
+    if opts.with_deps {
+        items = deps::with_deps(&src.file, items);
+    }
+    if opts.context_uses {
+        let mut uses = deps::context_uses(&src.file, &items);
+        uses.append(&mut items);
+        items = uses;
+    }
+    if opts.source_order {
+        items.sort_by_key(|item| src.range(item).start);
+    }
+    if let Some(None) = opts.rewrite_paths {
+        items.retain(|item| !transform::is_relative_use(item));
+    }
+    if opts.rename.is_some() && items.len() > 1 {
+        let msg = format!("--rename needs one item, found {}", items.len());
+        return Err(Error::Usage(msg));
+    }
+    if let Some(dir) = &opts.split_dir {
+        return split(src, opts, items, dir);
+    }
+    if opts.line_numbers && !opts.keep_comments {
+        warn("line numbers are approximate without --keep-comments");
+    }
+    let labels: Vec<_> = items.iter().map(item_label).collect();
+    for (item, label) in items.iter().zip(&labels) {
+        let line = item.span().start().line;
+        if let Some(block) = src.blocks.iter().find(|b| b.contains(&line)) {
+            eprintln!("{}: from the code block at line {}", label, block.start);
+        }
+    }
+    let has_fn = items.iter().any(|i| matches!(i, Item::Fn(_)));
+    let has_main = items
+        .iter()
+        .any(|i| matches!(i, Item::Fn(f) if f.sig.ident == "main"));
+    // runs of items printed together get one label for the toc and --verify
+    let (labels, rendered): (Vec<_>, Vec<_>) = match opts.span_gap {
+        true => span_gap(src, opts, items).into_iter().unzip(),
+        false => {
+            let rendered = items.into_iter().map(|i| finish(src, opts, i));
+            (labels, rendered.collect())
+        }
+    };
+    let mut first_line = 1;
+    if opts.doctest {
+        writeln!(out, "```rust")?;
+        first_line += 1;
+    }
+    if opts.rust_script {
+        write!(out, "{}", RUST_SCRIPT_HEADER)?;
+        first_line += RUST_SCRIPT_HEADER.lines().count();
+    }
+    if opts.toc {
+        let toc = toc(&labels, &rendered, first_line, &opts.separator);
+        write!(out, "{}", toc)?;
+    }
+    write!(out, "{}", rendered.join(&opts.separator))?;
+    if opts.rust_script && has_fn && !has_main {
+        write!(out, "{}", RUST_SCRIPT_MAIN)?;
+    }
+    if opts.doctest {
+        writeln!(out, "```")?;
+    }
+    if opts.verify {
+        verify(&labels, &rendered)?;
+    }
+    Ok(())
+}
+
+/// Writes each item to its own file in `dir`, named after the item. Names
+/// that were already used get the item kind appended. Each file imports its
+/// siblings through `use super::*`, so private items become `pub(super)` and
+/// `use` declarations go into mod.rs, which imports every item by name.
+fn split(
+    src: &Source,
+    opts: &ExtractOpts,
+    items: Vec<Item>,
+    dir: &Path,
+) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut used = HashSet::new();
+    let mut exported = HashMap::new();
+    let (mut mods, mut uses) = (String::new(), String::new());
+    for mut item in items {
+        if let Item::Use(_) = item {
+            uses += &finish(src, opts, item);
+            continue;
+        }
+        let (kind, name) = match (item_info(&item), &item) {
+            (Some((kind, name)), _) => (kind, name.to_string()),
+            (None, Item::Impl(i)) => ("impl", impl_name(i)),
+            _ => ("item", "item".to_string()),
+        };
+        // the stem is the module name in mod.rs too
+        let mut stem = mod_ident(snake_case(&file_stem(&name)));
+        if !used.insert(stem.clone()) {
+            stem = format!("{}_{}", stem, file_stem(kind));
+            let base = stem.clone();
+            // a second item of the same kind and name gets a number
+            for n in 2.. {
+                if used.insert(stem.clone()) {
+                    break;
                 }
+                stem = format!("{}_{}", base, n);
             }
         }
-        ExtractItem::Function { name } => {
-            print!("{}", extract::<ItemFn>(&file, &name))
+        // macros are only in scope in the files after their module
+        if let Item::Macro(_) = item {
+            mods += "#[macro_use]\n";
+        }
+        mods += &format!("mod {};\n", stem);
+        let vis = api::visibility(&item).cloned();
+        let ident = syn::parse_str::<Ident>(&name);
+        let module = syn::parse_str::<Ident>(&stem);
+        if let (Some(vis), Ok(ident), Ok(module)) = (vis, ident, module) {
+            // a name used again is imported with a number, like `Foo_2`
+            let alias = file_stem(&name);
+            let count = exported.entry(name).or_insert(0);
+            *count += 1;
+            let export: Item = match *count {
+                1 => parse_quote!(#vis use #module::#ident;),
+                n => {
+                    let alias = format!("{}_{}", alias, n);
+                    let alias = Ident::new(&alias, ident.span());
+                    parse_quote!(#vis use #module::#ident as #alias;)
+                }
+            };
+            uses += &export.unparse();
+        }
+        transform::widen_private(&mut item);
+        let text = format!("use super::*;\n\n{}", finish(src, opts, item));
+        fs::write(dir.join(stem + ".rs"), text)?;
+    }
+    let mod_rs = match uses.is_empty() {
+        true => mods,
+        false => format!("{}\n{}", mods, uses),
+    };
+    fs::write(dir.join("mod.rs"), mod_rs)?;
+    let plural = if used.len() == 1 { "" } else { "s" };
+    eprintln!(
+        "wrote {} file{} and mod.rs to {}",
+        used.len(),
+        plural,
+        dir.display()
+    );
+    Ok(())
+}
+
+/// Turns an item name into a file name, replacing anything but letters,
+/// digits and `_`
+fn file_stem(name: &str) -> String {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Converts a name like `HttpServer` or `HTTPServer` to `http_server`, so
+/// module names don't clash with the types they hold
+fn snake_case(name: &str) -> String {
+    let chars: Vec<_> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let starts_word = prev.is_some_and(|p| {
+            p.is_lowercase()
+                || p.is_ascii_digit()
+                || p.is_uppercase() && next.is_some_and(|n| n.is_lowercase())
+        });
+        if c.is_uppercase() && starts_word {
+            out.push('_');
         }
-        ExtractItem::Struct { name } => {
-            print!("{}", extract::<ItemStruct>(&file, &name))
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+const RUST_SCRIPT_HEADER: &str = r#"#!/usr/bin/env rust-script
+//! ```cargo
+//! [dependencies]
+//! # crates the code needs, like:
+//! # anyhow = "1"
+//! ```
+
+"#;
+
+const RUST_SCRIPT_MAIN: &str = "
+fn main() {
+    // call the extracted functions here
+    todo!()
+}
+";
+
+fn verify(labels: &[String], rendered: &[String]) -> Result<()> {
+    let errors: Vec<_> = labels
+        .iter()
+        .zip(rendered)
+        .filter_map(|(label, text)| {
+            let e = syn::parse_file(text).err()?;
+            let pos = e.span().start();
+            Some(format!("{} (line {}): {}", label, pos.line, e))
+        })
+        .collect();
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Verify(errors)),
+    }
+}
+
+/// Builds a comment listing each item with the output lines it will occupy,
+/// when the comment starts at `first_line` and items are separated by
+/// `separator`
+fn toc(
+    labels: &[String],
+    rendered: &[String],
+    first_line: usize,
+    separator: &str,
+) -> String {
+    // header, one line per item, and a blank line
+    let mut line = first_line + labels.len() + 2;
+    let mut toc = "// Contents:\n".to_string();
+    for (label, text) in labels.iter().zip(rendered) {
+        let end = line + text.lines().count().max(1) - 1;
+        toc += &format!("//   {} (lines {}-{})\n", label, line, end);
+        line = end + 1 + separator.matches('\n').count();
+    }
+    toc + "\n"
+}
+
+/// Applies the requested transforms to an extracted item and unparses it
+fn finish(src: &Source, opts: &ExtractOpts, mut item: Item) -> String {
+    let first_line = item.span().start().line;
+    if opts.debug_ast {
+        return format!("{:#?}\n", item);
+    }
+    let context = match opts.drop_cfg {
+        true => String::new(),
+        false => cfg_context(&src.file, &item),
+    };
+    let allow = opts.allow.as_deref().map(allow_attr);
+    // the attribute wasn't in the source, so it has no number
+    let allow_line = allow.as_ref().map(|a| unparse_attr(a) + "\n");
+    let allow_added: &[usize] = match allow_line {
+        Some(_) => &[0],
+        None => &[],
+    };
+    if opts.keep_comments {
+        let text = allow_line.unwrap_or_default()
+            + &verbatim(src, &item, opts.keep_attrs.as_deref());
+        return match opts.line_numbers {
+            true => context + &number_lines(&text, first_line, allow_added),
+            false => context + &text,
+        };
+    }
+    // to fall back to, if the transformed item can't be unparsed properly
+    let original = item.clone();
+    if opts.inline_aliases {
+        transform::inline_aliases(&src.file, &mut item);
+    }
+    if opts.normalize_generics {
+        transform::normalize_generics(&mut item);
+    }
+    if opts.canonical_bounds {
+        transform::canonical_bounds(&mut item);
+    }
+    if let Some(vis) = &opts.vis_bump {
+        let vis = syn::parse_str(vis).expect("checked by clap");
+        transform::bump_vis(&mut item, &vis, opts.vis_bump_fields);
+    }
+    if let Some(root) = &opts.rewrite_paths {
+        transform::rewrite_paths(&mut item, root.as_deref());
+    }
+    if let Some(name) = &opts.rename {
+        transform::rename(&mut item, name, opts.rename_refs);
+    }
+    for (old, new) in &opts.transform {
+        transform::rename_ident(&mut item, old, new);
+    }
+    if let (true, Item::Use(u)) = (opts.sort_use, &mut item) {
+        transform::sort_use_tree(&mut u.tree);
+    }
+    if opts.no_docs {
+        transform::strip_docs(&mut item);
+    }
+    if let (Some(keep), Some(attrs)) =
+        (&opts.keep_attrs, item_attrs_mut(&mut item))
+    {
+        attrs.retain(|attr| keep.iter().any(|k| attr_is(attr, k)));
+    }
+    if !opts.strip_derive.is_empty() {
+        transform::strip_derives(&mut item, &opts.strip_derive);
+    }
+    if let (true, Some(attrs)) = (opts.drop_cfg, item_attrs_mut(&mut item)) {
+        attrs.retain(|attr| !attr_is(attr, "cfg"));
+    }
+    if let (Some(allow), Some(attrs)) = (allow, item_attrs_mut(&mut item)) {
+        // after the doc comments
+        let docs = attrs.iter().take_while(|a| a.path.is_ident("doc"));
+        attrs.insert(docs.count(), allow);
+    }
+    let note = list::generics(&item)
+        .map(list::const_params)
+        .filter(|params| opts.show_const_generics && !params.is_empty())
+        .map(|params| format!("// const generics: {}\n", params.join(", ")));
+    let mut text = timing::UNPARSE.time(|| item.unparse());
+    // lines of the text that aren't in the source
+    let mut added: &[usize] = &[];
+    if syn::parse_file(&text).is_err() {
+        warn(&format!(
+            "{} doesn't parse after unparsing, printing its source instead",
+            item_label(&original)
+        ));
+        text = verbatim(src, &original, opts.keep_attrs.as_deref());
+        if let Some(allow) = allow_line {
+            text.insert_str(0, &allow);
+            added = allow_added;
         }
-        ExtractItem::Enum { name } => {
-            print!("{}", extract::<ItemEnum>(&file, &name))
+    }
+    if let Some(width) = opts.max_width {
+        text = format::reflow(&text, width);
+    }
+    if let Some(n) = opts.indent {
+        text = format::reindent(&text, &" ".repeat(n));
+    }
+    if opts.tabs {
+        text = format::reindent(&text, "\t");
+    }
+    if opts.compact {
+        text = format::compact(&text);
+    }
+    if opts.line_numbers {
+        text = number_lines(&text, first_line, added);
+    }
+    context + &note.unwrap_or_default() + &text
+}
+
+/// `#[allow]` of the lints, or of `dead_code` and `unused` if none are given
+fn allow_attr(lints: &[String]) -> Attribute {
+    let lints: Vec<syn::Path> = match lints {
+        [] => vec![parse_quote!(dead_code), parse_quote!(unused)],
+        _ => lints
+            .iter()
+            .map(|lint| syn::parse_str(lint).expect("checked by clap"))
+            .collect(),
+    };
+    parse_quote!(#[allow(#(#lints),*)])
+}
+
+/// Comments noting the `#[cfg]`s of the modules the item is in, which don't
+/// come along with it, like `// in mod imp, gated by #[cfg(unix)]`
+fn cfg_context(file: &File, item: &Item) -> String {
+    let mut context = String::new();
+    let mut path = vec![];
+    for m in cfg::enclosing(&file.items, item) {
+        path.push(m.ident.to_string());
+        for attr in m.attrs.iter().filter(|attr| attr_is(attr, "cfg")) {
+            context += &format!(
+                "// in mod {}, gated by {}\n",
+                path.join("::"),
+                unparse_attr(attr)
+            );
         }
-        ExtractItem::Trait { name } => {
-            print!("{}", extract::<ItemTrait>(&file, &name))
+    }
+    context
+}
+
+/// Prefixes the lines of `text` with right-aligned numbers, counting up from
+/// `first`, leaving the `added` lines, which aren't in the source, unnumbered
+fn number_lines(text: &str, first: usize, added: &[usize]) -> String {
+    let count = text.lines().count() - added.len();
+    let last = first + count.saturating_sub(1);
+    let width = last.to_string().len();
+    let mut out = String::new();
+    let mut n = first;
+    for (i, line) in text.lines().enumerate() {
+        if added.contains(&i) {
+            out += &format!("{:width$} | {}\n", "", line, width = width);
+        } else {
+            out += &format!("{:>width$} | {}\n", n, line, width = width);
+            n += 1;
         }
-        ExtractItem::Const { name } => {
-            print!("{}", extract::<ItemConst>(&file, &name))
+    }
+    out
+}
+
+/// Slices the original text of an item out of the source, cutting out the
+/// attributes that aren't in `keep`
+fn verbatim(src: &Source, item: &Item, keep: Option<&[String]>) -> String {
+    dedent(&slice(src, item, keep), item.span().start().column)
+}
+
+/// Slices runs of items with only whitespace and comments between them out
+/// of the source as a whole, with the labels of their items joined
+fn span_gap(
+    src: &Source,
+    opts: &ExtractOpts,
+    mut items: Vec<Item>,
+) -> Vec<(String, String)> {
+    items.sort_by_key(|item| src.range(item).start);
+    let keep = opts.keep_attrs.as_deref();
+    let allow = opts.allow.as_deref().map(|a| unparse_attr(&allow_attr(a)));
+    // start, labels, text and added `#[allow]` lines of each run, and where
+    // the last one ends
+    let mut runs: Vec<(LineColumn, Vec<String>, String, Vec<usize>)> = vec![];
+    let mut end = 0;
+    for item in &items {
+        let range = src.range(item);
+        let gap = src.text.get(end..range.start);
+        let start = item.span().start();
+        match (gap, runs.last_mut()) {
+            (Some(gap), Some((_, _, run, _))) if only_comments(gap) => {
+                *run += gap;
+            }
+            _ => runs.push((start, vec![], String::new(), vec![])),
         }
-        ExtractItem::ExternCrate { name } => {
-            print!("{}", extract::<ItemExternCrate>(&file, &name))
+        let (_, labels, run, added) = runs.last_mut().unwrap();
+        labels.push(item_label(item));
+        if let Some(allow) = &allow {
+            added.push(run.matches('\n').count());
+            *run += &format!("{}\n{}", allow, " ".repeat(start.column));
         }
-        ExtractItem::Static { name } => {
-            print!("{}", extract::<ItemStatic>(&file, &name))
+        *run += &slice(src, item, keep);
+        end = range.end;
+    }
+    runs.into_iter()
+        .map(|(start, labels, text, added)| {
+            let text = dedent(&text, start.column);
+            let text = match opts.line_numbers {
+                true => number_lines(&text, start.line, &added),
+                false => text,
+            };
+            (labels.join(", "), text)
+        })
+        .collect()
+}
+
+fn only_comments(text: &str) -> bool {
+    text.parse::<TokenStream>().is_ok_and(|t| t.is_empty())
+}
+
+fn slice(src: &Source, item: &Item, keep: Option<&[String]>) -> String {
+    if let Some((body, members)) = members(src, item) {
+        return slice_container(src, item, keep, body, &members);
+    }
+    let range = src.range(item);
+    let mut text = String::new();
+    let mut pos = range.start;
+    let dropped = item_attrs(item).iter().filter(|attr| {
+        keep.is_some_and(|k| !k.iter().any(|k| attr_is(attr, k)))
+    });
+    for attr in dropped {
+        let attr = src.range(attr);
+        text += &src.text[pos..attr.start];
+        let rest = &src.text[attr.end..range.end];
+        pos = range.end - rest.trim_start().len();
+    }
+    text += &src.text[pos..range.end];
+    text
+}
+
+/// A member of an impl, trait or extern block as sliced out of the source,
+/// with whether it needs a `;` added, for trait members whose default was
+/// stripped
+type MemberRange = (Range<usize>, bool);
+
+/// The range between the braces of an impl, trait or extern block, and the
+/// members it has left, which may be fewer than in the source
+fn members(
+    src: &Source,
+    item: &Item,
+) -> Option<(Range<usize>, Vec<MemberRange>)> {
+    let whole = |m: &dyn Spanned| (src.range(m), false);
+    let (brace, members) = match item {
+        Item::Impl(i) => (
+            i.brace_token.span,
+            i.items.iter().map(|m| whole(m)).collect(),
+        ),
+        Item::ForeignMod(f) => (
+            f.brace_token.span,
+            f.items.iter().map(|m| whole(m)).collect(),
+        ),
+        Item::Trait(t) => {
+            let members = t.items.iter().map(|member| {
+                let end = match member {
+                    TraitItem::Method(m) if m.default.is_none() => m.sig.span(),
+                    TraitItem::Const(c) if c.default.is_none() => c.ty.span(),
+                    TraitItem::Type(ty) if ty.default.is_none() => {
+                        match ty.bounds.last() {
+                            Some(bound) => bound.span(),
+                            None => ty.ident.span(),
+                        }
+                    }
+                    _ => return whole(member),
+                };
+                let start = src.offset(member.span().start());
+                (start..src.offset(end.end()), true)
+            });
+            (t.brace_token.span, members.collect())
         }
-        ExtractItem::Type { name } => {
-            print!("{}", extract::<ItemType>(&file, &name))
+        _ => return None,
+    };
+    let body = src.offset(brace.start()) + 1..src.offset(brace.end()) - 1;
+    Some((body, members))
+}
+
+/// Slices the members of a container out of the source, keeping the
+/// comments between ones that are next to each other, under a header
+/// rebuilt from the item, as its generics or trait may have been dropped
+fn slice_container(
+    src: &Source,
+    item: &Item,
+    keep: Option<&[String]>,
+    body: Range<usize>,
+    members: &[MemberRange],
+) -> String {
+    let mut header = item.clone();
+    if let Some(attrs) = item_attrs_mut(&mut header) {
+        attrs.retain(|attr| {
+            keep.is_none_or(|k| k.iter().any(|k| attr_is(attr, k)))
+        });
+    }
+    match &mut header {
+        Item::Impl(i) => i.items.clear(),
+        Item::ForeignMod(f) => f.items.clear(),
+        Item::Trait(t) => t.items.clear(),
+        _ => unreachable!("not a container"),
+    }
+    let header = header.unparse();
+    let header = header.trim_end().strip_suffix("{}").unwrap_or(&header);
+    // the source lines after the first are dedented by the item's column
+    let indent = " ".repeat(item.span().start().column);
+    let mut text = header.replace('\n', &format!("\n{}", indent)) + "{";
+    // the whitespace before the next line's code
+    let line_indent = |gap: &str| {
+        let last = &gap[gap.rfind('\n').map_or(gap.len(), |i| i + 1)..];
+        match last.trim().is_empty() {
+            true => last.to_string(),
+            false => indent.clone() + "    ",
         }
-        ExtractItem::Union { name } => {
-            print!("{}", extract::<ItemUnion>(&file, &name))
+    };
+    let mut pos = body.start;
+    for (i, (range, semi)) in members.iter().enumerate() {
+        let gap = &src.text[pos..range.start];
+        if only_comments(gap) {
+            text += gap;
+        } else {
+            // other members were here
+            text += if i == 0 { "\n" } else { "\n\n" };
+            text += &line_indent(gap);
         }
-        ExtractItem::Macro { name } => {
-            print!("{}", extract::<ItemMacro>(&file, &name))
+        text += &src.text[range.clone()];
+        if *semi {
+            text.push(';');
         }
+        pos = range.end;
     }
+    let gap = &src.text[pos..body.end];
+    match only_comments(gap) {
+        true => text += gap,
+        false => text += &("\n".to_string() + &indent),
+    }
+    text + "}"
 }
 
-fn extract<T: Find + Unparse + Clone>(file: &File, name: &str) -> String {
-    T::find(file, name).unwrap().clone().unparse()
+/// Later lines of an item keep their original indentation, removes what the
+/// first line had
+fn dedent(text: &str, indent: usize) -> String {
+    let mut lines = text.lines();
+    let mut out = lines.next().unwrap_or_default().to_string() + "\n";
+    for line in lines {
+        let trimmed = line.trim_start();
+        let strip = (line.len() - trimmed.len()).min(indent);
+        out += &line[strip..];
+        out.push('\n');
+    }
+    out
 }
 
 trait Find {
     fn find<'a>(file: &'a File, name: &str) -> Option<&'a Self> {
-        for item in &file.items {
-            if let Some(e) = Self::find_item(item, name) {
-                return Some(e);
-            }
-        }
-        None
+        Self::find_all(file, name).into_iter().next()
+    }
+    /// Finds items named `name` at the top level, or in inline modules and
+    /// function bodies for paths like `parser::lexer::tokenize` or `f::Local`
+    fn find_all<'a>(file: &'a File, name: &str) -> Vec<&'a Self> {
+        let (items, name) = modules::resolve(&file.items, name);
+        items
+            .into_iter()
+            .filter_map(|item| Self::find_item(item, name))
+            .collect()
     }
     fn find_item<'a>(item: &'a Item, name: &str) -> Option<&'a Self>;
 }
 
 trait Unparse: Sized {
-    fn as_item(self) -> Item;
+    fn into_item(self) -> Item;
     fn unparse(self) -> String {
         unparse(
             &(File {
                 shebang: None,
                 attrs: vec![],
-                items: vec![self.as_item()],
+                items: vec![self.into_item()],
             }),
         )
     }
 }
 
+/// Finds the signature of a function, or of a method given as `Type::name`
+fn fn_signature(
+    file: &File,
+    name: &str,
+    opts: &ExtractOpts,
+) -> Option<Signature> {
+    if let Some(Item::Fn(f)) = Kind::Fn.find(file, name, false).pop() {
+        return Some(f.sig);
+    }
+    let (ty, method) = name.rsplit_once("::")?;
+    match find_methods(file, Some(ty), method, false, opts).pop()? {
+        Item::Impl(mut i) => match i.items.pop()? {
+            ImplItem::Method(m) => Some(m.sig),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Renders a function argument on one line, like `&mut self` or
+/// `(a, b): (u8, u8)`
+fn unparse_arg(arg: &FnArg) -> String {
+    let item: Item = parse_quote!(fn _x(#arg) {});
+    let text = item.unparse();
+    let start = text.find('(').unwrap() + 1;
+    let end = text.rfind(')').unwrap();
+    // long arguments get broken up into lines with trailing commas, join
+    // them back
+    let mut arg = String::new();
+    for line in text[start..end].lines().map(str::trim) {
+        if line.starts_with(')') {
+            arg.truncate(arg.trim_end_matches([',', ' ']).len());
+        } else if !(arg.is_empty() || arg.ends_with('(')) {
+            arg.push(' ');
+        }
+        arg += line;
+    }
+    arg.trim_end_matches(',').to_string()
+}
+
+/// Renders an expression by unparsing it as the value of a dummy const
+fn unparse_expr(expr: &Expr) -> String {
+    let item: Item = parse_quote!(const _X: () = #expr;);
+    let text = item.unparse();
+    let text = text.trim_end().strip_prefix("const _X: () = ").unwrap();
+    text.strip_suffix(';').unwrap().to_string()
+}
+
+/// Renders an attribute by unparsing it on a dummy struct, so doc comments
+/// come out as `///` lines
+fn unparse_attr(attr: &Attribute) -> String {
+    let item: Item = parse_quote!(#attr struct _X;);
+    let text = item.unparse();
+    let text = text.trim_end().strip_suffix("struct _X;").unwrap();
+    text.trim_end().to_string()
+}
+
+/// Renders a type by unparsing it as a dummy type alias
+fn unparse_type(ty: &syn::Type) -> String {
+    let item: Item = parse_quote!(type _X = #ty;);
+    let text = item.unparse();
+    let text = text.trim_end().strip_prefix("type _X = ").unwrap();
+    text.strip_suffix(';').unwrap().to_string()
+}
+
 macro_rules! impl_traits {
     ($t:ty : Item:: $var:ident) => {
         impl Find for $t {
@@ -161,7 +3036,7 @@ macro_rules! impl_traits {
         }
 
         impl Unparse for $t {
-            fn as_item(self) -> Item {
+            fn into_item(self) -> Item {
                 Item::$var(self)
             }
         }
@@ -180,11 +3055,23 @@ impl Find for ItemFn {
 }
 
 impl Unparse for ItemFn {
-    fn as_item(self) -> Item {
+    fn into_item(self) -> Item {
         Item::Fn(self)
     }
 }
 
+impl Unparse for Item {
+    fn into_item(self) -> Item {
+        self
+    }
+}
+
+impl Unparse for ItemImpl {
+    fn into_item(self) -> Item {
+        Item::Impl(self)
+    }
+}
+
 impl Find for ItemMacro {
     fn find_item<'a>(item: &'a Item, name: &str) -> Option<&'a Self> {
         if let Item::Macro(f) = item {
@@ -197,7 +3084,7 @@ impl Find for ItemMacro {
 }
 
 impl Unparse for ItemMacro {
-    fn as_item(self) -> Item {
+    fn into_item(self) -> Item {
         Item::Macro(self)
     }
 }
@@ -205,8 +3092,175 @@ impl Unparse for ItemMacro {
 impl_traits!(ItemStruct: Item::Struct);
 impl_traits!(ItemEnum: Item::Enum);
 impl_traits!(ItemTrait: Item::Trait);
+impl_traits!(ItemTraitAlias: Item::TraitAlias);
 impl_traits!(ItemConst: Item::Const);
 impl_traits!(ItemExternCrate: Item::ExternCrate);
 impl_traits!(ItemStatic: Item::Static);
 impl_traits!(ItemType: Item::Type);
 impl_traits!(ItemUnion: Item::Union);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CFG_SOURCE: &str = r#"
+#[cfg(feature = "x")]
+/// Docs
+fn gated() {}
+
+#[cfg(unix)]
+mod imp {
+    #[cfg(target_os = "linux")]
+    pub fn inner() {}
+}
+"#;
+
+    /// Writes `text` to `name` in a temporary directory, returning its path
+    fn fixture(name: &str, text: &str) -> String {
+        let dir = env::temp_dir()
+            .join(format!("code-extractor-tests-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    /// Runs the command line `args` the way `main` does, returning the
+    /// output and the exit code of the error, if any
+    fn run_args(args: &[&str]) -> (String, Option<i32>) {
+        let opt = Opt::parse_from(["code-extractor"].iter().chain(args));
+        let task = Task::Command(opt.item.as_ref().unwrap());
+        let mut out = vec![];
+        let result = run_to(&opt, &task, &mut out);
+        (
+            String::from_utf8(out).unwrap(),
+            result.err().map(|e| e.exit_code()),
+        )
+    }
+
+    /// Extracts the function `name` with the given flags
+    fn extract(flags: &[&str], name: &str) -> String {
+        let args = ["code-extractor", "x.rs"].iter().chain(flags);
+        let opt = Opt::parse_from(args.chain(&["function", name]));
+        let file = syn::parse_file(CFG_SOURCE).unwrap();
+        let src = Source::new(CFG_SOURCE.to_string(), file);
+        let item = Kind::Fn.find(&src.file, name, false).pop().unwrap();
+        finish(&src, &opt.extract, item)
+    }
+
+    #[test]
+    fn cfg_round_trip() {
+        let text = extract(&[], "gated");
+        assert_eq!(text, "#[cfg(feature = \"x\")]\n/// Docs\nfn gated() {}\n");
+        let text = extract(&["--keep-comments"], "gated");
+        assert_eq!(text, "#[cfg(feature = \"x\")]\n/// Docs\nfn gated() {}\n");
+    }
+
+    #[test]
+    fn enclosing_cfg_noted() {
+        let text = extract(&[], "imp::inner");
+        assert_eq!(
+            text,
+            "// in mod imp, gated by #[cfg(unix)]\n\
+             #[cfg(target_os = \"linux\")]\npub fn inner() {}\n"
+        );
+        let text = extract(&["--drop-cfg"], "imp::inner");
+        assert_eq!(text, "pub fn inner() {}\n");
+    }
+
+    #[test]
+    fn empty_file() {
+        let path = fixture("empty.rs", "");
+        let (out, code) = run_args(&[&path, "list"]);
+        assert_eq!(out, "Listing items:\nNo items found\n");
+        assert_eq!(code, None);
+        let (out, code) = run_args(&[&path, "function", "main"]);
+        assert_eq!(out, "");
+        assert_eq!(code, Some(error::EXIT_NOT_FOUND));
+    }
+
+    #[test]
+    fn comments_only_file() {
+        let path = fixture("comments.rs", "// a comment\n/* and a block */\n");
+        let (out, code) = run_args(&[&path, "list"]);
+        assert_eq!(out, "Listing items:\nNo items found\n");
+        assert_eq!(code, None);
+        let (out, code) = run_args(&[&path, "struct", "Config"]);
+        assert_eq!(out, "");
+        assert_eq!(code, Some(error::EXIT_NOT_FOUND));
+    }
+
+    #[test]
+    fn exit_code_not_found() {
+        let path = fixture("codes.rs", "fn parse() {}\n");
+        let (_, code) = run_args(&[&path, "function", "lex"]);
+        assert_eq!(code, Some(error::EXIT_NOT_FOUND));
+    }
+
+    #[test]
+    fn exit_code_parse() {
+        let path = fixture("invalid.rs", "fn parse( {}\n");
+        let (_, code) = run_args(&[&path, "function", "parse"]);
+        assert_eq!(code, Some(error::EXIT_PARSE));
+    }
+
+    #[test]
+    fn exit_code_io() {
+        let path = fixture("present.rs", "fn parse() {}\n");
+        let missing = path.replace("present.rs", "missing.rs");
+        let (_, code) = run_args(&[&missing, "function", "parse"]);
+        assert_eq!(code, Some(error::EXIT_IO));
+    }
+
+    #[test]
+    fn exit_code_usage() {
+        let text =
+            "#[cfg(unix)]\nfn parse() {}\n#[cfg(windows)]\nfn parse() {}\n";
+        let path = fixture("twice.rs", text);
+        let args = [&path, "--all", "--rename", "lex", "function", "parse"];
+        let (_, code) = run_args(&args);
+        assert_eq!(code, Some(error::EXIT_USAGE));
+    }
+
+    #[test]
+    fn exit_code_check() {
+        let path = fixture("undocumented.rs", "pub fn parse() {}\n");
+        let args = [&path, "list", "--undocumented", "--strict"];
+        let (out, code) = run_args(&args);
+        assert!(out.contains("parse"));
+        assert_eq!(code, Some(error::EXIT_CHECK));
+    }
+
+    #[test]
+    fn deterministic_output() {
+        let text = "
+use std::collections::HashMap;
+
+struct Config { names: HashMap<String, Entry>, root: Node }
+struct Entry(Vec<Node>);
+enum Node { Leaf(Entry), Branch(Vec<Node>) }
+
+fn load(config: &Config) -> Node { parse(&config.root) }
+fn parse(node: &Node) -> Node { check(node); node.clone() }
+fn check(_: &Node) {}
+impl Config { fn new() -> Self { todo!() } }
+";
+        let path = fixture("deterministic.rs", text);
+        let queries: [&[&str]; 5] = [
+            &["list"],
+            &["--with-deps", "struct", "Config"],
+            &["--with-deps", "function", "load"],
+            &["graph"],
+            &["hashes"],
+        ];
+        for query in queries {
+            let args: Vec<_> = [&*path].iter().chain(query).copied().collect();
+            let (first, code) = run_args(&args);
+            assert_eq!(code, None, "{:?}", query);
+            assert!(!first.is_empty());
+            for _ in 0..5 {
+                assert_eq!(run_args(&args).0, first, "{:?}", query);
+            }
+        }
+    }
+}
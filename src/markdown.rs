@@ -0,0 +1,98 @@
+//! Pulling the Rust code out of Markdown files
+
+use std::ops::Range;
+
+/// Tags of rustdoc code blocks that don't change the language
+const RUST_TAGS: &[&str] = &["rust", "no_run", "should_panic", "compile_fail"];
+
+/// Blanks out everything but the Rust code blocks, so line numbers stay the
+/// same, and returns the text with the line ranges of the blocks. Untagged
+/// blocks count as Rust like in rustdoc, `ignore` and `text` ones don't.
+/// Hidden lines (`# code`) are kept. Blocks that don't parse on their own,
+/// like doctests made of statements, are left out.
+pub fn rust_blocks(text: &str) -> (String, Vec<Range<usize>>) {
+    let mut out = String::new();
+    let mut blocks = vec![];
+    // the fence that opened the current block, whether it's Rust, and its
+    // code and first line
+    let mut open: Option<(&str, bool, String, usize)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let n = i + 1;
+        let trimmed = line.trim_start();
+        match &mut open {
+            Some((fence, rust, code, start)) => {
+                if closes(trimmed, fence) {
+                    if *rust {
+                        add_block(&mut out, &mut blocks, code, *start..n);
+                    }
+                    open = None;
+                    out.push('\n');
+                } else if *rust {
+                    *code += hidden(line);
+                    code.push('\n');
+                } else {
+                    // other languages are blanked out like prose
+                    out.push('\n');
+                }
+            }
+            None => {
+                let fence = ['`', '~'].into_iter().find_map(|c| {
+                    let len =
+                        trimmed.len() - trimmed.trim_start_matches(c).len();
+                    (len >= 3).then(|| &trimmed[..len])
+                });
+                if let Some(fence) = fence {
+                    let rust = is_rust(&trimmed[fence.len()..]);
+                    open = Some((fence, rust, String::new(), n + 1));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    // a block that isn't closed runs to the end
+    if let Some((_, true, code, start)) = open {
+        let end = start + code.lines().count();
+        add_block(&mut out, &mut blocks, &code, start..end);
+    }
+    (out, blocks)
+}
+
+/// Whether `line` is a fence closing a block opened with `fence`, which
+/// takes at least as many of the same characters
+fn closes(line: &str, fence: &str) -> bool {
+    let line = line.trim_end();
+    let c = fence.chars().next().unwrap_or('`');
+    line.starts_with(fence) && line.trim_start_matches(c).is_empty()
+}
+
+fn add_block(
+    out: &mut String,
+    blocks: &mut Vec<Range<usize>>,
+    code: &str,
+    lines: Range<usize>,
+) {
+    match syn::parse_file(code) {
+        Ok(_) => {
+            *out += code;
+            blocks.push(lines);
+        }
+        Err(_) => *out += &"\n".repeat(code.lines().count()),
+    }
+}
+
+fn is_rust(info: &str) -> bool {
+    let mut tags = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty());
+    tags.all(|t| RUST_TAGS.contains(&t) || t.starts_with("edition"))
+}
+
+/// Strips the `# ` of lines rustdoc hides, keeping attributes like `#[test]`
+fn hidden(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    match trimmed.strip_prefix('#') {
+        Some("") => "",
+        Some(rest) if rest.starts_with(' ') => &rest[1..],
+        _ => line,
+    }
+}
@@ -0,0 +1,182 @@
+use proc_macro2::{LineColumn, Span};
+use syn::{
+    spanned::Spanned, File, Item, ItemFn, ItemForeignMod, ItemImpl, ItemMod,
+    ItemTrait, Stmt,
+};
+
+/// Calls `f` with the path and contents of every inline module in `items`,
+/// recursively, in source order
+pub fn walk<'a>(
+    items: &'a [Item],
+    path: &mut Vec<String>,
+    f: &mut impl FnMut(&[String], &'a ItemMod),
+) {
+    for item in items {
+        if let Item::Mod(m) = item {
+            if let Some((_, content)) = &m.content {
+                path.push(m.ident.to_string());
+                f(path, m);
+                walk(content, path, f);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// Splits a path like `parser::lexer::tokenize` into the items of the inline
+/// module `parser::lexer` and the name `tokenize`. Segments can also name
+/// functions, for items defined in their body, like `f::Local`. Names
+/// without `::` are looked up in `items` itself, unknown paths give no items.
+pub fn resolve<'a, 'n>(
+    items: &'a [Item],
+    path: &'n str,
+) -> (Vec<&'a Item>, &'n str) {
+    let mut items: Vec<_> = items.iter().collect();
+    let (modules, name) = match path.rsplit_once("::") {
+        Some(split) => split,
+        None => return (items, path),
+    };
+    let mut segments = modules.split("::").peekable();
+    segments.next_if_eq(&"crate");
+    for segment in segments {
+        let inner = items.iter().find_map(|item| match item {
+            Item::Mod(m) if m.ident == segment => {
+                m.content.as_ref().map(|(_, c)| c.iter().collect())
+            }
+            Item::Fn(f) if f.sig.ident == segment => Some(local_items(f)),
+            _ => None,
+        });
+        items = match inner {
+            Some(inner) => inner,
+            None => return (vec![], name),
+        };
+    }
+    (items, name)
+}
+
+/// Items defined directly in a function's body
+fn local_items(f: &ItemFn) -> Vec<&Item> {
+    let stmts = f.block.stmts.iter();
+    stmts
+        .filter_map(|stmt| match stmt {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds the innermost item containing `pos`, descending into modules and
+/// function bodies. Members of impls, traits and `extern` blocks are returned
+/// wrapped in a copy of their parent with just them inside.
+pub fn item_at<'a>(
+    items: impl IntoIterator<Item = &'a Item>,
+    pos: LineColumn,
+) -> Option<Item> {
+    let item = items.into_iter().find(|item| contains(item.span(), pos))?;
+    let inner = match item {
+        Item::Mod(m) => m.content.as_ref().and_then(|(_, c)| item_at(c, pos)),
+        Item::Fn(f) => item_at(local_items(f), pos),
+        Item::Impl(i) => {
+            let member = i.items.iter().find(|m| contains(m.span(), pos));
+            member.map(|member| {
+                Item::Impl(ItemImpl {
+                    items: vec![member.clone()],
+                    ..i.clone()
+                })
+            })
+        }
+        Item::Trait(t) => {
+            let member = t.items.iter().find(|m| contains(m.span(), pos));
+            member.map(|member| {
+                Item::Trait(ItemTrait {
+                    items: vec![member.clone()],
+                    ..t.clone()
+                })
+            })
+        }
+        Item::ForeignMod(b) => {
+            let member = b.items.iter().find(|m| contains(m.span(), pos));
+            member.map(|member| {
+                Item::ForeignMod(ItemForeignMod {
+                    items: vec![member.clone()],
+                    ..b.clone()
+                })
+            })
+        }
+        _ => None,
+    };
+    Some(inner.unwrap_or_else(|| item.clone()))
+}
+
+fn contains(span: Span, pos: LineColumn) -> bool {
+    let (start, end) = (span.start(), span.end());
+    let pos = (pos.line, pos.column);
+    (start.line, start.column) <= pos && pos < (end.line, end.column)
+}
+
+/// Builds a file out of the items of all modules whose path matches
+/// `pattern`, or `None` if nothing matched. Modules inside a matching one
+/// already come along with its items, so they aren't added again.
+pub fn scope(file: File, pattern: &str) -> Option<File> {
+    let mut items = vec![];
+    let mut matched: Vec<Vec<String>> = vec![];
+    walk(&file.items, &mut vec![], &mut |path, m| {
+        let inside = matched.iter().any(|outer| path.starts_with(outer));
+        if !inside && glob_match(pattern, &path.join("::")) {
+            matched.push(path.to_vec());
+            items.extend(m.content.iter().flat_map(|(_, c)| c).cloned());
+        }
+    });
+    (!matched.is_empty()).then(|| File { items, ..file })
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters,
+/// including `::`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+    use crate::item_info;
+
+    #[test]
+    fn scope_nested_glob() {
+        let file: File = parse_quote! {
+            mod internal {
+                mod a {
+                    fn in_a() {}
+                    mod b {
+                        fn in_b() {}
+                    }
+                }
+                mod c {
+                    fn in_c() {}
+                }
+            }
+        };
+        let scoped = scope(file, "internal::*").unwrap();
+        let names: Vec<_> = scoped
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Mod(m) => m.ident.to_string(),
+                _ => item_info(item).unwrap().1.to_string(),
+            })
+            .collect();
+        assert_eq!(names, ["in_a", "b", "in_c"]);
+    }
+}
@@ -0,0 +1,88 @@
+//! Finding the uses of `std` that stand in the way of `no_std`
+
+use std::{collections::BTreeSet, io::Write};
+
+use owo_colors::{OwoColorize, Stream::Stdout};
+use syn::{
+    visit::{self, Visit},
+    File, Item, ItemUse, Path, UseTree,
+};
+
+use crate::{error::Result, item_label};
+
+/// Prints each top-level item referring to `std::` paths with the paths, then
+/// how many items do
+pub fn report(file: &File, out: &mut dyn Write) -> Result<()> {
+    let mut count = 0;
+    for item in &file.items {
+        let mut paths = StdPaths::default();
+        paths.visit_item(item);
+        if paths.found.is_empty() {
+            continue;
+        }
+        count += 1;
+        let label = match item {
+            Item::Use(_) => "use declaration".to_string(),
+            _ => item_label(item),
+        };
+        writeln!(out, "{}", label.if_supports_color(Stdout, |l| l.purple()))?;
+        for path in &paths.found {
+            writeln!(out, "    {}", path)?;
+        }
+    }
+    let total = file.items.len();
+    writeln!(out, "{} of {} items use std", count, total)?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct StdPaths {
+    found: BTreeSet<String>,
+}
+
+impl<'ast> Visit<'ast> for StdPaths {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if path.segments.first().is_some_and(|s| s.ident == "std") {
+            let segments = path.segments.iter().map(|s| s.ident.to_string());
+            self.found.insert(segments.collect::<Vec<_>>().join("::"));
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_item_use(&mut self, i: &'ast ItemUse) {
+        if let UseTree::Path(p) = &i.tree {
+            if p.ident == "std" {
+                use_paths(&i.tree, "", &mut self.found);
+            }
+        }
+    }
+}
+
+/// Expands a `use` tree into the paths it imports, like `std::io::Read` for
+/// `std::io::{Read, Write}`
+fn use_paths(tree: &UseTree, prefix: &str, found: &mut BTreeSet<String>) {
+    let join = |name: &dyn ToString| match prefix {
+        "" => name.to_string(),
+        _ => format!("{}::{}", prefix, name.to_string()),
+    };
+    match tree {
+        UseTree::Path(p) => use_paths(&p.tree, &join(&p.ident), found),
+        UseTree::Name(n) if n.ident == "self" => {
+            found.insert(prefix.to_string());
+        }
+        UseTree::Name(n) => {
+            found.insert(join(&n.ident));
+        }
+        UseTree::Rename(r) => {
+            found.insert(join(&r.ident));
+        }
+        UseTree::Glob(_) => {
+            found.insert(join(&"*"));
+        }
+        UseTree::Group(g) => {
+            for tree in &g.items {
+                use_paths(tree, prefix, found);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Matching function signatures against patterns like
+//! `fn(_, &str) -> Result<_, _>`, where `_` matches any type
+
+use quote::ToTokens;
+use syn::{
+    FnArg, GenericArgument, PathArguments, ReturnType, Signature, Type,
+    TypeBareFn,
+};
+
+use crate::error::{Error, Result};
+
+pub fn parse_pattern(pattern: &str) -> Result<TypeBareFn> {
+    syn::parse_str(pattern).map_err(|e| {
+        Error::Usage(format!("invalid signature pattern `{}`: {}", pattern, e))
+    })
+}
+
+/// Whether `sig` has as many arguments as the pattern, each matching it, and
+/// a matching return type. A pattern without `->` only matches functions
+/// returning `()`, `-> _` matches any.
+pub fn matches(pattern: &TypeBareFn, sig: &Signature) -> bool {
+    if pattern.inputs.len() != sig.inputs.len() {
+        return false;
+    }
+    let mut args = pattern.inputs.iter().zip(&sig.inputs);
+    let args_match = args.all(|(pat, arg)| match arg {
+        FnArg::Typed(arg) => type_matches(&pat.ty, &arg.ty),
+        // receivers only match a wildcard
+        FnArg::Receiver(_) => matches!(pat.ty, Type::Infer(_)),
+    });
+    args_match && return_matches(&pattern.output, &sig.output)
+}
+
+fn return_matches(pattern: &ReturnType, output: &ReturnType) -> bool {
+    let unit = |ty: &Type| matches!(ty, Type::Tuple(t) if t.elems.is_empty());
+    match (pattern, output) {
+        (ReturnType::Default, ReturnType::Default) => true,
+        (ReturnType::Default, ReturnType::Type(_, ty)) => unit(ty),
+        (ReturnType::Type(_, pat), ReturnType::Default) => {
+            unit(pat) || matches!(**pat, Type::Infer(_))
+        }
+        (ReturnType::Type(_, pat), ReturnType::Type(_, ty)) => {
+            type_matches(pat, ty)
+        }
+    }
+}
+
+/// Compares types by shape. Paths match on their last segment, and a path
+/// without generic arguments matches any arguments, so `Result` matches
+/// `io::Result<()>`.
+fn type_matches(pattern: &Type, ty: &Type) -> bool {
+    match (pattern, ty) {
+        (Type::Infer(_), _) => true,
+        (Type::Group(p), _) => type_matches(&p.elem, ty),
+        (_, Type::Group(t)) => type_matches(pattern, &t.elem),
+        (Type::Paren(p), _) => type_matches(&p.elem, ty),
+        (_, Type::Paren(t)) => type_matches(pattern, &t.elem),
+        (Type::Path(p), Type::Path(t)) if p.qself.is_none() => {
+            let (p, t) = match (p.path.segments.last(), t.path.segments.last())
+            {
+                (Some(p), Some(t)) => (p, t),
+                _ => return false,
+            };
+            p.ident == t.ident && arguments_match(&p.arguments, &t.arguments)
+        }
+        (Type::Reference(p), Type::Reference(t)) => {
+            p.mutability.is_some() == t.mutability.is_some()
+                && type_matches(&p.elem, &t.elem)
+        }
+        (Type::Ptr(p), Type::Ptr(t)) => {
+            p.mutability.is_some() == t.mutability.is_some()
+                && type_matches(&p.elem, &t.elem)
+        }
+        (Type::Slice(p), Type::Slice(t)) => type_matches(&p.elem, &t.elem),
+        (Type::Array(p), Type::Array(t)) => type_matches(&p.elem, &t.elem),
+        (Type::Tuple(p), Type::Tuple(t)) => {
+            p.elems.len() == t.elems.len()
+                && p.elems
+                    .iter()
+                    .zip(&t.elems)
+                    .all(|(p, t)| type_matches(p, t))
+        }
+        _ => tokens(pattern) == tokens(ty),
+    }
+}
+
+fn arguments_match(pattern: &PathArguments, args: &PathArguments) -> bool {
+    let (pattern, args) = match (pattern, args) {
+        (PathArguments::None, _) => return true,
+        (
+            PathArguments::AngleBracketed(p),
+            PathArguments::AngleBracketed(a),
+        ) => (p, a),
+        _ => return tokens(pattern) == tokens(args),
+    };
+    // lifetimes don't matter for the shape
+    let non_lifetimes = |args: &syn::AngleBracketedGenericArguments| {
+        args.args
+            .iter()
+            .filter(|a| !matches!(a, GenericArgument::Lifetime(_)))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let (pattern, args) = (non_lifetimes(pattern), non_lifetimes(args));
+    pattern.len() == args.len()
+        && pattern.iter().zip(&args).all(|pair| match pair {
+            (GenericArgument::Type(p), GenericArgument::Type(t)) => {
+                type_matches(p, t)
+            }
+            (p, t) => tokens(p) == tokens(t),
+        })
+}
+
+fn tokens(t: &dyn ToTokens) -> String {
+    let s = t.to_token_stream().to_string();
+    s.split_whitespace().collect()
+}
@@ -0,0 +1,45 @@
+use std::ops::Range;
+
+use proc_macro2::LineColumn;
+use syn::{spanned::Spanned, File};
+
+/// A parsed input file along with its text, for slicing out the original
+/// code of nodes
+pub struct Source {
+    pub text: String,
+    pub file: File,
+    /// Line ranges of the code blocks the text came from, for
+    /// `--from-markdown`
+    pub blocks: Vec<Range<usize>>,
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    pub fn new(text: String, file: File) -> Self {
+        let line_starts = [0]
+            .into_iter()
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            text,
+            file,
+            blocks: vec![],
+            line_starts,
+        }
+    }
+
+    /// Byte offset of a position reported by a span
+    pub fn offset(&self, pos: LineColumn) -> usize {
+        let start = self.line_starts[pos.line - 1];
+        self.text[start..]
+            .char_indices()
+            .nth(pos.column)
+            .map_or(self.text.len(), |(i, _)| start + i)
+    }
+
+    /// Byte range of a node, from its first to its last token
+    pub fn range(&self, node: &(impl Spanned + ?Sized)) -> Range<usize> {
+        let span = node.span();
+        self.offset(span.start())..self.offset(span.end())
+    }
+}
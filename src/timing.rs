@@ -0,0 +1,47 @@
+//! Phase timings for `--time`, summed over all input files, so with
+//! several jobs they can add up to more than the wall time
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+pub struct Phase(AtomicU64);
+
+pub static READ: Phase = Phase::new();
+pub static PARSE: Phase = Phase::new();
+pub static UNPARSE: Phase = Phase::new();
+/// Whole files, the rest of which is searching
+pub static FILES: Phase = Phase::new();
+
+impl Phase {
+    const fn new() -> Self {
+        Phase(AtomicU64::new(0))
+    }
+
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let nanos = start.elapsed().as_nanos() as u64;
+        self.0.fetch_add(nanos, Ordering::Relaxed);
+        result
+    }
+
+    fn total(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+pub fn report() {
+    let (read, parse, unparse) = (READ.total(), PARSE.total(), UNPARSE.total());
+    let search = FILES.total().saturating_sub(read + parse + unparse);
+    eprintln!("timings:");
+    for (phase, time) in [
+        ("reading", read),
+        ("parsing", parse),
+        ("searching", search),
+        ("unparsing", unparse),
+    ] {
+        eprintln!("{:>12} {:.2?}", phase, time);
+    }
+}
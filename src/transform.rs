@@ -0,0 +1,764 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use proc_macro2::Span;
+use quote::{quote, ToTokens};
+use syn::{
+    parse_quote,
+    punctuated::Punctuated,
+    visit_mut::{self, VisitMut},
+    Attribute, Block, ConstParam, Field, File, ForeignItem, GenericArgument,
+    GenericParam, Generics, Ident, ImplItem, Item, ItemMod, ItemTrait,
+    ItemType, ItemUse, Lifetime, Meta, NestedMeta, Path, PathArguments,
+    PathSegment, PredicateLifetime, PredicateType, Stmt, TraitItem, Type,
+    TypeParam, TypePath, UsePath, UseTree, Variant, Visibility, WherePredicate,
+};
+
+use crate::{attr_is, is_cfg_test, item_attrs, item_attrs_mut, item_info};
+
+/// Expansion depth after which alias inlining gives up, in case of aliases
+/// that (invalidly) refer to themselves
+const MAX_ALIAS_DEPTH: usize = 64;
+
+/// Replaces uses of the file's top-level type aliases inside `item` with the
+/// aliased types, substituting the alias' generic arguments.
+pub fn inline_aliases(file: &File, item: &mut Item) {
+    let aliases = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Type(t) => Some((t.ident.to_string(), t)),
+            _ => None,
+        })
+        .collect();
+    InlineAliases { aliases, depth: 0 }.visit_item_mut(item);
+}
+
+struct InlineAliases<'a> {
+    aliases: HashMap<String, &'a ItemType>,
+    depth: usize,
+}
+
+impl VisitMut for InlineAliases<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        let expanded = match ty {
+            Type::Path(TypePath { qself: None, path })
+                if path.leading_colon.is_none() && path.segments.len() == 1 =>
+            {
+                let segment = &path.segments[0];
+                self.aliases
+                    .get(&segment.ident.to_string())
+                    .map(|alias| expand(alias, &segment.arguments))
+            }
+            _ => None,
+        };
+        match expanded {
+            Some(expanded) if self.depth < MAX_ALIAS_DEPTH => {
+                *ty = expanded;
+                self.depth += 1;
+                visit_mut::visit_type_mut(self, ty);
+                self.depth -= 1;
+            }
+            _ => visit_mut::visit_type_mut(self, ty),
+        }
+    }
+}
+
+/// Instantiates an alias' definition with the arguments it was used with,
+/// falling back to the parameters' defaults
+fn expand(alias: &ItemType, args: &PathArguments) -> Type {
+    let args: Vec<_> = match args {
+        PathArguments::AngleBracketed(args) => args.args.iter().collect(),
+        _ => vec![],
+    };
+    let mut types = args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(t) => Some(t.clone()),
+        _ => None,
+    });
+    let mut lifetimes = args.iter().filter_map(|arg| match arg {
+        GenericArgument::Lifetime(l) => Some(l.clone()),
+        _ => None,
+    });
+    let mut subst = Substitute::default();
+    for param in &alias.generics.params {
+        match param {
+            GenericParam::Type(p) => {
+                if let Some(ty) = types.next().or_else(|| p.default.clone()) {
+                    subst.types.insert(p.ident.to_string(), ty);
+                }
+            }
+            GenericParam::Lifetime(p) => {
+                if let Some(lt) = lifetimes.next() {
+                    subst.lifetimes.insert(p.lifetime.ident.to_string(), lt);
+                }
+            }
+            GenericParam::Const(_) => {}
+        }
+    }
+    let mut ty = (*alias.ty).clone();
+    subst.visit_type_mut(&mut ty);
+    ty
+}
+
+#[derive(Default)]
+struct Substitute {
+    types: HashMap<String, Type>,
+    lifetimes: HashMap<String, Lifetime>,
+}
+
+impl VisitMut for Substitute {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if let Some(ident) = path.get_ident() {
+                if let Some(replacement) = self.types.get(&ident.to_string()) {
+                    *ty = replacement.clone();
+                    return;
+                }
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_lifetime_mut(&mut self, lt: &mut Lifetime) {
+        if let Some(replacement) = self.lifetimes.get(&lt.ident.to_string()) {
+            *lt = replacement.clone();
+        }
+    }
+}
+
+/// Sorts `use` declarations among themselves by path, keeping them ahead of
+/// any other items, and sorts the groups inside them, like rustfmt would.
+pub fn sort_uses(items: &mut [Item]) {
+    for item in items.iter_mut() {
+        if let Item::Use(u) = item {
+            sort_use_tree(&mut u.tree);
+        }
+    }
+    items.sort_by_cached_key(|item| match item {
+        Item::Use(u) => (0, u.tree.to_token_stream().to_string()),
+        _ => (1, String::new()),
+    });
+}
+
+/// Recursively sorts the groups of a `use` tree, with `self` first and
+/// globs last.
+pub fn sort_use_tree(tree: &mut UseTree) {
+    match tree {
+        UseTree::Path(p) => sort_use_tree(&mut p.tree),
+        UseTree::Group(g) => {
+            let mut trees: Vec<_> = std::mem::take(&mut g.items)
+                .into_iter()
+                .map(|mut tree| {
+                    sort_use_tree(&mut tree);
+                    tree
+                })
+                .collect();
+            trees.sort_by_cached_key(use_tree_key);
+            g.items = trees.into_iter().collect::<Punctuated<_, _>>();
+        }
+        UseTree::Name(_) | UseTree::Rename(_) | UseTree::Glob(_) => {}
+    }
+}
+
+fn use_tree_key(tree: &UseTree) -> (u8, String) {
+    let name = |ident: &syn::Ident| ident.to_string();
+    match tree {
+        UseTree::Name(n) if n.ident == "self" => (0, String::new()),
+        UseTree::Path(p) => (1, name(&p.ident)),
+        UseTree::Name(n) => (1, name(&n.ident)),
+        UseTree::Rename(r) => (1, name(&r.ident)),
+        UseTree::Group(_) => (2, String::new()),
+        UseTree::Glob(_) => (3, String::new()),
+    }
+}
+
+/// Renames the item itself to `name`. With `refs`, every identifier equal to
+/// the old name inside the item is renamed too, catching recursive calls and
+/// `Foo::new()`-style references.
+pub fn rename(item: &mut Item, name: &str, refs: bool) {
+    let ident = match item_ident_mut(item) {
+        Some(ident) => ident,
+        None => return,
+    };
+    let old = ident.to_string();
+    *ident = Ident::new(name, ident.span());
+    if refs {
+        Rename { old, name }.visit_item_mut(item);
+    }
+}
+
+/// Renames every occurrence of the identifier `old` in the item, whether it
+/// names the item, something inside it, or something it refers to
+pub fn rename_ident(item: &mut Item, old: &str, name: &str) {
+    let old = old.to_string();
+    Rename { old, name }.visit_item_mut(item);
+}
+
+fn item_ident_mut(item: &mut Item) -> Option<&mut Ident> {
+    match item {
+        Item::Const(i) => Some(&mut i.ident),
+        Item::Enum(i) => Some(&mut i.ident),
+        Item::ExternCrate(i) => Some(&mut i.ident),
+        Item::Fn(i) => Some(&mut i.sig.ident),
+        Item::Macro(i) => i.ident.as_mut(),
+        Item::Mod(i) => Some(&mut i.ident),
+        Item::Static(i) => Some(&mut i.ident),
+        Item::Struct(i) => Some(&mut i.ident),
+        Item::Trait(i) => Some(&mut i.ident),
+        Item::TraitAlias(i) => Some(&mut i.ident),
+        Item::Type(i) => Some(&mut i.ident),
+        Item::Union(i) => Some(&mut i.ident),
+        _ => None,
+    }
+}
+
+struct Rename<'a> {
+    old: String,
+    name: &'a str,
+}
+
+impl VisitMut for Rename<'_> {
+    fn visit_ident_mut(&mut self, ident: &mut Ident) {
+        if *ident == self.old {
+            *ident = Ident::new(self.name, ident.span());
+        }
+    }
+}
+
+/// Renames the generic parameters of the item to `T0, T1, ...` for types,
+/// `'a0, 'a1, ...` for lifetimes and `N0, N1, ...` for consts, everywhere in
+/// the item, so items differing only in parameter names unparse the same.
+pub fn normalize_generics(item: &mut Item) {
+    let generics = match generics_mut(item) {
+        Some(generics) => generics,
+        None => return,
+    };
+    let mut idents = HashMap::new();
+    let mut lifetimes = HashMap::new();
+    let (mut types, mut lts, mut consts) = (0, 0, 0);
+    for param in &generics.params {
+        match param {
+            GenericParam::Type(p) => {
+                idents.insert(p.ident.to_string(), format!("T{}", types));
+                types += 1;
+            }
+            GenericParam::Lifetime(p) => {
+                let name = p.lifetime.ident.to_string();
+                lifetimes.insert(name, format!("a{}", lts));
+                lts += 1;
+            }
+            GenericParam::Const(p) => {
+                idents.insert(p.ident.to_string(), format!("N{}", consts));
+                consts += 1;
+            }
+        }
+    }
+    Normalize { idents, lifetimes }.visit_item_mut(item);
+}
+
+fn generics_mut(item: &mut Item) -> Option<&mut Generics> {
+    match item {
+        Item::Enum(i) => Some(&mut i.generics),
+        Item::Fn(i) => Some(&mut i.sig.generics),
+        Item::Impl(i) => Some(&mut i.generics),
+        Item::Struct(i) => Some(&mut i.generics),
+        Item::Trait(i) => Some(&mut i.generics),
+        Item::TraitAlias(i) => Some(&mut i.generics),
+        Item::Type(i) => Some(&mut i.generics),
+        Item::Union(i) => Some(&mut i.generics),
+        _ => None,
+    }
+}
+
+/// Renames the declarations of the parameters and the paths starting with
+/// them, in types, expressions and patterns, but not fields, methods or
+/// locals that happen to share a name
+struct Normalize {
+    idents: HashMap<String, String>,
+    /// Lifetime names without the `'`
+    lifetimes: HashMap<String, String>,
+}
+
+impl Normalize {
+    fn rename(&self, ident: &mut Ident) {
+        if let Some(name) = self.idents.get(&ident.to_string()) {
+            *ident = Ident::new(name, ident.span());
+        }
+    }
+}
+
+impl VisitMut for Normalize {
+    fn visit_type_param_mut(&mut self, param: &mut TypeParam) {
+        self.rename(&mut param.ident);
+        visit_mut::visit_type_param_mut(self, param);
+    }
+
+    fn visit_const_param_mut(&mut self, param: &mut ConstParam) {
+        self.rename(&mut param.ident);
+        visit_mut::visit_const_param_mut(self, param);
+    }
+
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        if let (None, Some(first)) =
+            (path.leading_colon, path.segments.first_mut())
+        {
+            self.rename(&mut first.ident);
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if let Some(name) = self.lifetimes.get(&lifetime.ident.to_string()) {
+            lifetime.ident = Ident::new(name, lifetime.ident.span());
+        }
+    }
+}
+
+/// Sets the visibility of the item, or of the members of an inherent impl,
+/// and with `fields` of the fields of a struct or union too
+pub fn bump_vis(item: &mut Item, vis: &Visibility, fields: bool) {
+    each_vis(item, fields, &mut |v| *v = vis.clone());
+}
+
+/// Makes the private item, its fields and the members of an inherent impl
+/// `pub(super)`, so the other files of a split module can reach them
+pub fn widen_private(item: &mut Item) {
+    let vis: Visibility = parse_quote!(pub(super));
+    each_vis(item, true, &mut |v| {
+        if let Visibility::Inherited = v {
+            *v = vis.clone();
+        }
+    });
+}
+
+fn each_vis(
+    item: &mut Item,
+    fields: bool,
+    set: &mut dyn FnMut(&mut Visibility),
+) {
+    match item {
+        Item::Const(i) => set(&mut i.vis),
+        Item::Enum(i) => set(&mut i.vis),
+        Item::Fn(i) => set(&mut i.vis),
+        Item::Mod(i) => set(&mut i.vis),
+        Item::Static(i) => set(&mut i.vis),
+        Item::Struct(i) => {
+            set(&mut i.vis);
+            if fields {
+                i.fields.iter_mut().for_each(|f| set(&mut f.vis));
+            }
+        }
+        Item::Trait(i) => set(&mut i.vis),
+        Item::TraitAlias(i) => set(&mut i.vis),
+        Item::Type(i) => set(&mut i.vis),
+        Item::Union(i) => {
+            set(&mut i.vis);
+            if fields {
+                let fields = i.fields.named.iter_mut();
+                fields.for_each(|f| set(&mut f.vis));
+            }
+        }
+        Item::Impl(i) if i.trait_.is_none() => {
+            for member in &mut i.items {
+                match member {
+                    ImplItem::Const(c) => set(&mut c.vis),
+                    ImplItem::Method(m) => set(&mut m.vis),
+                    ImplItem::Type(t) => set(&mut t.vis),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rewrites paths starting with `crate::`, `self::` or `super::` in the
+/// item to start with `root` instead. Without a root, the prefix and the
+/// lowercase segments after it, which by convention name modules, are
+/// removed, so the paths refer to the other extracted items, and `use`
+/// declarations of such paths are dropped.
+pub fn rewrite_paths(item: &mut Item, root: Option<&str>) {
+    let root = root.map(|root| Ident::new(root, Span::call_site()));
+    RewritePaths { root }.visit_item_mut(item);
+}
+
+/// Whether the item is a `use` of a path relative to the crate
+pub fn is_relative_use(item: &Item) -> bool {
+    match item {
+        Item::Use(u) => match &u.tree {
+            UseTree::Path(p) => is_relative(&p.ident),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_relative(ident: &Ident) -> bool {
+    ident == "crate" || ident == "self" || ident == "super"
+}
+
+struct RewritePaths {
+    root: Option<Ident>,
+}
+
+impl VisitMut for RewritePaths {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        let prefix = path
+            .segments
+            .iter()
+            .take_while(|segment| is_relative(&segment.ident))
+            .count();
+        // a lone `self` is the receiver, and `crate` or `super` alone
+        // appear in visibilities
+        if prefix > 0 && prefix < path.segments.len() {
+            let rest: Vec<_> =
+                path.segments.iter().skip(prefix).cloned().collect();
+            path.segments = match &self.root {
+                Some(root) => std::iter::once(PathSegment::from(root.clone()))
+                    .chain(rest)
+                    .collect(),
+                None => {
+                    let modules = rest[..rest.len() - 1]
+                        .iter()
+                        .take_while(|s| starts_lowercase(&s.ident))
+                        .count();
+                    rest.into_iter().skip(modules).collect()
+                }
+            };
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_item_use_mut(&mut self, u: &mut ItemUse) {
+        let root = match &self.root {
+            Some(root) => root,
+            None => return,
+        };
+        let mut tree = &u.tree;
+        while let UseTree::Path(p) = tree {
+            if !is_relative(&p.ident) {
+                break;
+            }
+            tree = &p.tree;
+        }
+        if !std::ptr::eq(tree, &u.tree) {
+            u.tree = UseTree::Path(UsePath {
+                ident: root.clone(),
+                colon2_token: Default::default(),
+                tree: Box::new(tree.clone()),
+            });
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        if self.root.is_none() {
+            block.stmts.retain(|stmt| match stmt {
+                Stmt::Item(item) => !is_relative_use(item),
+                _ => true,
+            });
+        }
+        visit_mut::visit_block_mut(self, block);
+    }
+
+    fn visit_item_mod_mut(&mut self, m: &mut ItemMod) {
+        if let (None, Some((_, items))) = (&self.root, &mut m.content) {
+            items.retain(|item| !is_relative_use(item));
+        }
+        visit_mut::visit_item_mod_mut(self, m);
+    }
+
+    // paths in `pub(in crate::a)` have to stay relative
+    fn visit_visibility_mut(&mut self, _: &mut Visibility) {}
+}
+
+fn starts_lowercase(ident: &Ident) -> bool {
+    let name = ident.to_string();
+    let name = name.strip_prefix("r#").unwrap_or(&name);
+    name.starts_with(|c: char| c.is_lowercase() || c == '_')
+}
+
+/// Moves the bounds of generic parameters, like `T: Clone` in `impl<T:
+/// Clone>`, into the where clause, ahead of the predicates already there.
+/// This applies to all generics inside the item, including those of methods.
+pub fn canonical_bounds(item: &mut Item) {
+    CanonicalBounds.visit_item_mut(item);
+}
+
+struct CanonicalBounds;
+
+impl VisitMut for CanonicalBounds {
+    fn visit_generics_mut(&mut self, generics: &mut Generics) {
+        let mut moved = vec![];
+        for param in &mut generics.params {
+            match param {
+                GenericParam::Type(p) if !p.bounds.is_empty() => {
+                    let ident = &p.ident;
+                    moved.push(WherePredicate::Type(PredicateType {
+                        lifetimes: None,
+                        bounded_ty: parse_quote!(#ident),
+                        colon_token: Default::default(),
+                        bounds: std::mem::take(&mut p.bounds),
+                    }));
+                    p.colon_token = None;
+                }
+                GenericParam::Lifetime(p) if !p.bounds.is_empty() => {
+                    moved.push(WherePredicate::Lifetime(PredicateLifetime {
+                        lifetime: p.lifetime.clone(),
+                        colon_token: Default::default(),
+                        bounds: std::mem::take(&mut p.bounds),
+                    }));
+                    p.colon_token = None;
+                }
+                _ => {}
+            }
+        }
+        if !moved.is_empty() {
+            let clause = generics.make_where_clause();
+            let existing = std::mem::take(&mut clause.predicates);
+            clause.predicates = moved.into_iter().chain(existing).collect();
+        }
+        visit_mut::visit_generics_mut(self, generics);
+    }
+}
+
+/// Hoists the items of inline modules to the top level, recursively. Paths
+/// into the flattened modules lose their module and `crate`, `self` or
+/// `super` segments, `pub(super)`-like visibilities become `pub(crate)`, and
+/// `use` declarations of the modules' items, which are now in scope, are
+/// dropped along with duplicate ones. Returns the names that would be
+/// defined more than once if there are any.
+pub fn flatten_mods(items: Vec<Item>) -> Result<Vec<Item>, Vec<String>> {
+    let mut modules = HashSet::new();
+    let mut flat = vec![];
+    hoist(items, &mut modules, &mut flat);
+    let mut names = HashSet::new();
+    let mut dups = BTreeSet::new();
+    let mut uses = HashSet::new();
+    let mut items = vec![];
+    for mut item in flat {
+        if let Item::Use(u) = &item {
+            let key = u.tree.to_token_stream().to_string();
+            if is_local_use(&u.tree, &modules) || !uses.insert(key) {
+                continue;
+            }
+        }
+        if let Some((_, name)) = item_info(&item) {
+            if !names.insert(name.to_string()) {
+                dups.insert(name.to_string());
+            }
+        }
+        Flatten { modules: &modules }.visit_item_mut(&mut item);
+        items.push(item);
+    }
+    match dups.is_empty() {
+        true => Ok(items),
+        false => Err(dups.into_iter().map(|n| format!("`{}`", n)).collect()),
+    }
+}
+
+/// Whether a `use` tree refers to the flattened modules, or with `self` or
+/// `super` to items of the module it was in
+fn is_local_use(tree: &UseTree, modules: &HashSet<Ident>) -> bool {
+    let mut tree = tree;
+    while let UseTree::Path(p) = tree {
+        if p.ident == "self" || p.ident == "super" {
+            return true;
+        }
+        if p.ident != "crate" {
+            return modules.contains(&p.ident);
+        }
+        tree = &p.tree;
+    }
+    false
+}
+
+fn hoist(items: Vec<Item>, modules: &mut HashSet<Ident>, out: &mut Vec<Item>) {
+    for item in items {
+        match item {
+            Item::Mod(ItemMod {
+                ident,
+                content: Some((_, content)),
+                ..
+            }) => {
+                modules.insert(ident);
+                hoist(content, modules, out);
+            }
+            item => out.push(item),
+        }
+    }
+}
+
+struct Flatten<'a> {
+    modules: &'a HashSet<Ident>,
+}
+
+impl VisitMut for Flatten<'_> {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        let modules = path
+            .segments
+            .iter()
+            .take(path.segments.len().saturating_sub(1))
+            .take_while(|s| {
+                is_relative(&s.ident) || self.modules.contains(&s.ident)
+            })
+            .count();
+        if modules > 0 && path.leading_colon.is_none() {
+            path.segments =
+                path.segments.iter().skip(modules).cloned().collect();
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_visibility_mut(&mut self, vis: &mut Visibility) {
+        if let Visibility::Restricted(_) = vis {
+            *vis = parse_quote!(pub(crate));
+        }
+    }
+}
+
+/// Reduces a trait to its contract: the declarations of its associated
+/// items, without default bodies, values and types, or attributes
+pub fn trait_contract(t: &mut ItemTrait) {
+    for member in &mut t.items {
+        match member {
+            TraitItem::Const(c) => {
+                c.attrs.clear();
+                c.default = None;
+            }
+            TraitItem::Method(m) => {
+                m.attrs.clear();
+                m.default = None;
+                m.semi_token = Some(Default::default());
+            }
+            TraitItem::Type(ty) => {
+                ty.attrs.clear();
+                ty.default = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Removes `#[cfg(test)]` items, like `mod tests`, and `#[test]` functions
+/// from the file, including ones in inline modules
+pub fn strip_tests(file: &mut File) {
+    StripTests.visit_file_mut(file);
+}
+
+struct StripTests;
+
+impl StripTests {
+    fn strip(items: &mut Vec<Item>) {
+        items.retain(|item| {
+            let attrs = item_attrs(item);
+            let test = attrs.iter().any(|attr| attr_is(attr, "test"));
+            !(is_cfg_test(attrs) || test && matches!(item, Item::Fn(_)))
+        });
+    }
+}
+
+impl VisitMut for StripTests {
+    fn visit_file_mut(&mut self, file: &mut File) {
+        Self::strip(&mut file.items);
+        visit_mut::visit_file_mut(self, file);
+    }
+
+    fn visit_item_mod_mut(&mut self, m: &mut ItemMod) {
+        if let Some((_, items)) = &mut m.content {
+            Self::strip(items);
+        }
+        visit_mut::visit_item_mod_mut(self, m);
+    }
+}
+
+/// Removes the `#[doc]` attributes, which doc comments are, of the item and
+/// of everything inside it that can have them
+pub fn strip_docs(item: &mut Item) {
+    StripDocs.visit_item_mut(item);
+}
+
+struct StripDocs;
+
+fn retain_non_docs(attrs: &mut Vec<Attribute>) {
+    attrs.retain(|attr| !attr.path.is_ident("doc"));
+}
+
+impl VisitMut for StripDocs {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if let Some(attrs) = item_attrs_mut(item) {
+            retain_non_docs(attrs);
+        }
+        visit_mut::visit_item_mut(self, item);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        retain_non_docs(&mut field.attrs);
+        visit_mut::visit_field_mut(self, field);
+    }
+
+    fn visit_variant_mut(&mut self, variant: &mut Variant) {
+        retain_non_docs(&mut variant.attrs);
+        visit_mut::visit_variant_mut(self, variant);
+    }
+
+    fn visit_impl_item_mut(&mut self, member: &mut ImplItem) {
+        match member {
+            ImplItem::Const(i) => retain_non_docs(&mut i.attrs),
+            ImplItem::Method(i) => retain_non_docs(&mut i.attrs),
+            ImplItem::Type(i) => retain_non_docs(&mut i.attrs),
+            ImplItem::Macro(i) => retain_non_docs(&mut i.attrs),
+            _ => {}
+        }
+        visit_mut::visit_impl_item_mut(self, member);
+    }
+
+    fn visit_trait_item_mut(&mut self, member: &mut TraitItem) {
+        match member {
+            TraitItem::Const(i) => retain_non_docs(&mut i.attrs),
+            TraitItem::Method(i) => retain_non_docs(&mut i.attrs),
+            TraitItem::Type(i) => retain_non_docs(&mut i.attrs),
+            TraitItem::Macro(i) => retain_non_docs(&mut i.attrs),
+            _ => {}
+        }
+        visit_mut::visit_trait_item_mut(self, member);
+    }
+
+    fn visit_foreign_item_mut(&mut self, member: &mut ForeignItem) {
+        match member {
+            ForeignItem::Fn(i) => retain_non_docs(&mut i.attrs),
+            ForeignItem::Static(i) => retain_non_docs(&mut i.attrs),
+            ForeignItem::Type(i) => retain_non_docs(&mut i.attrs),
+            ForeignItem::Macro(i) => retain_non_docs(&mut i.attrs),
+            _ => {}
+        }
+        visit_mut::visit_foreign_item_mut(self, member);
+    }
+}
+
+/// Removes the derives named in `derives` from the item's `#[derive]`s,
+/// matching paths like `serde::Serialize` by their last segment. Derives left
+/// empty are dropped.
+pub fn strip_derives(item: &mut Item, derives: &[String]) {
+    let attrs = match item_attrs_mut(item) {
+        Some(attrs) => attrs,
+        None => return,
+    };
+    attrs.retain_mut(|attr| {
+        let list = match attr.parse_meta() {
+            Ok(Meta::List(list)) if list.path.is_ident("derive") => list,
+            _ => return true,
+        };
+        let kept: Vec<_> = list
+            .nested
+            .iter()
+            .filter(|nested| match nested {
+                NestedMeta::Meta(Meta::Path(path)) => path
+                    .segments
+                    .last()
+                    .is_none_or(|s| !derives.iter().any(|d| s.ident == d)),
+                _ => true,
+            })
+            .collect();
+        attr.tokens = quote!((#(#kept),*));
+        !kept.is_empty()
+    });
+}